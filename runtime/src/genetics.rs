@@ -0,0 +1,407 @@
+//! Kitty genetics, shared by the runtime and native clients so they agree on how a
+//! kitty looks.
+//!
+//! The 32 bytes of a kitty's DNA are eight trait slots of four genes (bytes) each:
+//!
+//! | Bytes    | Slot          |
+//! |----------|---------------|
+//! | `0..4`   | body colour   |
+//! | `4..8`   | pattern       |
+//! | `8..12`  | eye shape     |
+//! | `12..16` | fur           |
+//! | `16..20` | accessory 1   |
+//! | `20..24` | accessory 2   |
+//! | `24..28` | accessory 3   |
+//! | `28..32` | reserved      |
+//!
+//! The first gene of a slot is dominant and decides the trait the kitty shows. The
+//! other three are recessive: they are not visible, but are passed on when breeding.
+//!
+//! A gene below `MUTATION_BASE` is a base gene and picks one of the eight base variants
+//! of its trait with `gene % 8`. A gene from `MUTATION_BASE` up is a mutation and picks
+//! one of the four mutated variants with `(gene - MUTATION_BASE) / 8`.
+//!
+//! Mutations never appear in gen 0 kitties that players create or that exist from genesis,
+//! see `strip_mutations`. They come about in `splice`, when the dominant genes of both
+//! parents form a mutation pair. The one exception is `mint_promo`: the chain operator can
+//! hand out promo kitties with any DNA, mutations included, within the promo quota.
+
+#[cfg(feature = "std")]
+use serde_derive::{Deserialize, Serialize};
+
+/// Number of bytes in a kitty's DNA.
+pub const DNA_LENGTH: usize = 32;
+/// Number of genes in each trait slot, the dominant one first.
+pub const GENES_PER_SLOT: usize = 4;
+/// Number of trait slots in a kitty's DNA.
+pub const SLOTS: usize = DNA_LENGTH / GENES_PER_SLOT;
+/// Number of accessories a kitty can wear.
+pub const ACCESSORY_SLOTS: usize = 3;
+/// The lowest gene value that is a mutation.
+pub const MUTATION_BASE: u8 = 0xe0;
+
+/// Index of each trait slot in the DNA.
+pub mod slot {
+  pub const BODY_COLOUR: usize = 0;
+  pub const PATTERN: usize = 1;
+  pub const EYE_SHAPE: usize = 2;
+  pub const FUR: usize = 3;
+  pub const ACCESSORIES: [usize; super::ACCESSORY_SLOTS] = [4, 5, 6];
+  pub const RESERVED: usize = 7;
+}
+
+/// Whether `gene` is a mutation rather than a base gene.
+pub fn is_mutation(gene: u8) -> bool {
+  gene >= MUTATION_BASE
+}
+
+/// The gene at `index` (0 being the dominant one) of `slot`. Bytes missing from a short
+/// `dna` read as zero.
+pub fn gene(dna: &[u8], slot: usize, index: usize) -> u8 {
+  dna.get(slot * GENES_PER_SLOT + index).cloned().unwrap_or(0)
+}
+
+/// Turns every mutation in `dna` back into a base gene, so that players can only obtain
+/// mutated traits through breeding.
+pub fn strip_mutations(dna: &mut [u8]) {
+  for gene in dna.iter_mut() {
+    *gene %= MUTATION_BASE;
+  }
+}
+
+/// The mutation two dominant genes can produce, if any: a pair of base genes whose
+/// variants differ only in their lowest bit, such as variants 2 and 3, may produce the
+/// mutated variant with half their index.
+pub fn mutation_of(a: u8, b: u8) -> Option<u8> {
+  if is_mutation(a) || is_mutation(b) {
+    return None;
+  }
+
+  let (a, b) = (a % 8, b % 8);
+  if a ^ 1 == b {
+    Some(a / 2)
+  } else {
+    None
+  }
+}
+
+/// Writes the DNA of the child of `matron` and `sire` to `child`.
+///
+/// For every slot, each parent first lets its recessive genes surface: from the back,
+/// every gene swaps places with the one in front of it with a chance of one in four.
+/// The child then takes each of its four genes from one parent or the other. Finally,
+/// if the (surfaced) dominant genes of the parents form a mutation pair, the child's
+/// dominant gene mutates with a chance of `mutation_chance` in 256.
+///
+/// All the chances are drawn from the bits of `seed`, the same number of bits for every
+/// slot, so the outcome depends on nothing but the arguments.
+pub fn splice(matron: &[u8], sire: &[u8], seed: &[u8], mutation_chance: u8, child: &mut [u8]) {
+  let mut bits = Bits { seed: seed, position: 0 };
+
+  for slot in 0..SLOTS {
+    let matron_genes = surface_recessive(slot_genes(matron, slot), &mut bits);
+    let sire_genes = surface_recessive(slot_genes(sire, slot), &mut bits);
+
+    let mut genes = [0u8; GENES_PER_SLOT];
+    for (index, gene) in genes.iter_mut().enumerate() {
+      *gene = if bits.take(1) == 0 { matron_genes[index] } else { sire_genes[index] };
+    }
+
+    let roll = bits.take(8);
+    let variant = bits.take(3);
+    if let Some(mutation) = mutation_of(matron_genes[0], sire_genes[0]) {
+      if roll < mutation_chance {
+        genes[0] = MUTATION_BASE + mutation * 8 + variant;
+      }
+    }
+
+    for (index, gene) in genes.iter().enumerate() {
+      if let Some(byte) = child.get_mut(slot * GENES_PER_SLOT + index) {
+        *byte = *gene;
+      }
+    }
+  }
+}
+
+fn slot_genes(dna: &[u8], slot: usize) -> [u8; GENES_PER_SLOT] {
+  [gene(dna, slot, 0), gene(dna, slot, 1), gene(dna, slot, 2), gene(dna, slot, 3)]
+}
+
+fn surface_recessive(mut genes: [u8; GENES_PER_SLOT], bits: &mut Bits) -> [u8; GENES_PER_SLOT] {
+  for index in (1..GENES_PER_SLOT).rev() {
+    if bits.take(2) == 0 {
+      genes.swap(index, index - 1);
+    }
+  }
+
+  genes
+}
+
+/// Reads a seed a few bits at a time, starting over when it runs out.
+struct Bits<'a> {
+  seed: &'a [u8],
+  position: usize,
+}
+
+impl<'a> Bits<'a> {
+  fn take(&mut self, count: usize) -> u8 {
+    if self.seed.is_empty() {
+      return 0;
+    }
+
+    let mut value = 0;
+    for _ in 0..count {
+      let byte = self.seed[(self.position / 8) % self.seed.len()];
+      value = (value << 1) | ((byte >> (self.position % 8)) & 1);
+      self.position += 1;
+    }
+
+    value
+  }
+}
+
+macro_rules! gene_trait {
+  (
+    $(#[$attr:meta])*
+    pub enum $name:ident {
+      base: [$($base:ident),* $(,)*],
+      mutated: [$($mutated:ident),* $(,)*],
+    }
+  ) => {
+    $(#[$attr])*
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+    pub enum $name {
+      $($base,)*
+      $($mutated,)*
+    }
+
+    impl $name {
+      const BASE: [$name; 8] = [$($name::$base),*];
+      const MUTATED: [$name; 4] = [$($name::$mutated),*];
+
+      /// The variant `gene` expresses.
+      pub fn from_gene(gene: u8) -> Self {
+        if is_mutation(gene) {
+          Self::MUTATED[((gene - MUTATION_BASE) / 8) as usize]
+        } else {
+          Self::BASE[(gene % 8) as usize]
+        }
+      }
+    }
+  }
+}
+
+gene_trait! {
+  pub enum BodyColour {
+    base: [Shadow, Salmon, Mauve, Cottoncandy, Greymatter, Cinnamon, Aquamarine, Orangesoda],
+    mutated: [Royalpurple, Bananacream, Dragonfruit, Mintgreen],
+  }
+}
+
+gene_trait! {
+  pub enum Pattern {
+    base: [Solid, Tiger, Spotted, Tabby, Calico, Totesbasic, Rascal, Leopard],
+    mutated: [Camo, Amur, Jaguar, Harbourfog],
+  }
+}
+
+gene_trait! {
+  pub enum EyeShape {
+    base: [Round, Almond, Wide, Sleepy, Crazy, Thicccbrowz, Wonky, Slyboots],
+    mutated: [Googly, Wingtips, Chronic, Stunned],
+  }
+}
+
+gene_trait! {
+  pub enum Fur {
+    base: [Short, Long, Curly, Fluffy, Wiry, Silky, Sleek, Matted],
+    mutated: [Himalayan, Sphynx, Rex, Cosmic],
+  }
+}
+
+gene_trait! {
+  pub enum Accessory {
+    base: [Nothing, Bowtie, Collar, Bell, Scarf, Beanie, Monocle, Glasses],
+    mutated: [Crown, Halo, Wings, Jetpack],
+  }
+}
+
+/// How rare a kitty looks, going by the number of mutated traits it shows.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum Rarity {
+  Common,
+  Uncommon,
+  Rare,
+  Epic,
+  Legendary,
+}
+
+impl Rarity {
+  fn from_mutations(mutations: usize) -> Self {
+    match mutations {
+      0 => Rarity::Common,
+      1 => Rarity::Uncommon,
+      2 => Rarity::Rare,
+      3 => Rarity::Epic,
+      _ => Rarity::Legendary,
+    }
+  }
+}
+
+/// The traits a kitty shows.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct Phenotype {
+  pub body_colour: BodyColour,
+  pub pattern: Pattern,
+  pub eye_shape: EyeShape,
+  pub fur: Fur,
+  pub accessories: [Accessory; ACCESSORY_SLOTS],
+  pub rarity: Rarity,
+}
+
+impl Phenotype {
+  /// Decodes the traits expressed by the dominant genes of `dna`.
+  pub fn from_dna(dna: &[u8]) -> Self {
+    let dominant = |s| gene(dna, s, 0);
+
+    let mutations = (0..slot::RESERVED)
+      .filter(|&s| is_mutation(dominant(s)))
+      .count();
+
+    Phenotype {
+      body_colour: BodyColour::from_gene(dominant(slot::BODY_COLOUR)),
+      pattern: Pattern::from_gene(dominant(slot::PATTERN)),
+      eye_shape: EyeShape::from_gene(dominant(slot::EYE_SHAPE)),
+      fur: Fur::from_gene(dominant(slot::FUR)),
+      accessories: [
+        Accessory::from_gene(dominant(slot::ACCESSORIES[0])),
+        Accessory::from_gene(dominant(slot::ACCESSORIES[1])),
+        Accessory::from_gene(dominant(slot::ACCESSORIES[2])),
+      ],
+      rarity: Rarity::from_mutations(mutations),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn dominant_genes_decide_the_traits() {
+    let mut dna = [0u8; DNA_LENGTH];
+    dna[0] = 2;
+    // Recessive genes are not expressed
+    dna[1] = 7;
+    dna[4] = 9;
+    dna[8] = 0xdf;
+    dna[12] = 5;
+    dna[16] = 1;
+    dna[24] = 7;
+
+    let phenotype = Phenotype::from_dna(&dna);
+
+    assert_eq!(phenotype.body_colour, BodyColour::Mauve);
+    assert_eq!(phenotype.pattern, Pattern::Tiger);
+    assert_eq!(phenotype.eye_shape, EyeShape::Slyboots);
+    assert_eq!(phenotype.fur, Fur::Silky);
+    assert_eq!(phenotype.accessories, [Accessory::Bowtie, Accessory::Nothing, Accessory::Glasses]);
+    assert_eq!(phenotype.rarity, Rarity::Common);
+  }
+
+  #[test]
+  fn mutated_genes_make_kitties_rarer() {
+    let mut dna = [0u8; DNA_LENGTH];
+    dna[0] = MUTATION_BASE;
+    dna[4] = 0xff;
+
+    let phenotype = Phenotype::from_dna(&dna);
+    assert_eq!(phenotype.body_colour, BodyColour::Royalpurple);
+    assert_eq!(phenotype.pattern, Pattern::Harbourfog);
+    assert_eq!(phenotype.rarity, Rarity::Rare);
+
+    // The reserved slot is not shown, so it does not count
+    dna[28] = 0xf0;
+    assert_eq!(Phenotype::from_dna(&dna).rarity, Rarity::Rare);
+
+    dna[16] = 0xe8;
+    dna[20] = 0xf0;
+    assert_eq!(Phenotype::from_dna(&dna).rarity, Rarity::Legendary);
+  }
+
+  #[test]
+  fn splice_is_deterministic() {
+    let matron = [0x12u8; DNA_LENGTH];
+    let sire = [0x35u8; DNA_LENGTH];
+    let seed = [0x5au8; DNA_LENGTH];
+
+    let mut first = [0u8; DNA_LENGTH];
+    let mut second = [0u8; DNA_LENGTH];
+    splice(&matron, &sire, &seed, 128, &mut first);
+    splice(&matron, &sire, &seed, 128, &mut second);
+
+    assert_eq!(first, second);
+  }
+
+  #[test]
+  fn children_inherit_genes_of_the_same_slot() {
+    let mut matron = [0u8; DNA_LENGTH];
+    let mut sire = [0u8; DNA_LENGTH];
+    for i in 0..DNA_LENGTH {
+      matron[i] = i as u8;
+      sire[i] = 100 + i as u8;
+    }
+
+    let mut child = [0u8; DNA_LENGTH];
+    splice(&matron, &sire, &[0x9cu8; DNA_LENGTH], 0, &mut child);
+
+    for slot in 0..SLOTS {
+      let parent_genes = [slot_genes(&matron, slot), slot_genes(&sire, slot)];
+      for index in 0..GENES_PER_SLOT {
+        let inherited = gene(&child, slot, index);
+        assert!(parent_genes.iter().any(|genes| genes.contains(&inherited)));
+      }
+    }
+  }
+
+  #[test]
+  fn mutation_pairs_can_mutate() {
+    assert_eq!(mutation_of(2, 3), Some(1));
+    assert_eq!(mutation_of(14, 7), Some(3));
+    assert_eq!(mutation_of(1, 2), None);
+    assert_eq!(mutation_of(MUTATION_BASE, MUTATION_BASE + 1), None);
+
+    // An all zero seed rolls zero for every chance
+    let mut child = [0u8; DNA_LENGTH];
+    splice(&[2u8; DNA_LENGTH], &[3u8; DNA_LENGTH], &[0u8; DNA_LENGTH], 1, &mut child);
+    let phenotype = Phenotype::from_dna(&child);
+    assert_eq!(phenotype.body_colour, BodyColour::Bananacream);
+    assert_eq!(phenotype.rarity, Rarity::Legendary);
+
+    // Without a chance, nothing mutates
+    splice(&[2u8; DNA_LENGTH], &[3u8; DNA_LENGTH], &[0u8; DNA_LENGTH], 0, &mut child);
+    assert_eq!(Phenotype::from_dna(&child).rarity, Rarity::Common);
+
+    // And neither do genes that are not a pair
+    splice(&[2u8; DNA_LENGTH], &[4u8; DNA_LENGTH], &[0u8; DNA_LENGTH], 255, &mut child);
+    assert_eq!(Phenotype::from_dna(&child).rarity, Rarity::Common);
+  }
+
+  #[test]
+  fn gen0_dna_has_no_mutations() {
+    let mut dna = [0xff, MUTATION_BASE, 0x10];
+    strip_mutations(&mut dna);
+    assert_eq!(dna, [0x1f, 0, 0x10]);
+  }
+
+  #[test]
+  fn short_dna_reads_as_zero_genes() {
+    let phenotype = Phenotype::from_dna(&[3]);
+
+    assert_eq!(phenotype.body_colour, BodyColour::Cottoncandy);
+    assert_eq!(phenotype.accessories, [Accessory::Nothing; ACCESSORY_SLOTS]);
+  }
+}
@@ -51,6 +51,9 @@ mod template;
 
 mod substratekitties;
 
+/// How kitty DNA maps to traits, shared with native clients.
+pub mod genetics;
+
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
 /// of data like extrinsics, allowing for them to continue syncing the network through upgrades
@@ -82,7 +85,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("substratekitties"),
 	impl_name: create_runtime_str!("substratekitties"),
 	authoring_version: 3,
-	spec_version: 3,
+	spec_version: 4,
 	impl_version: 0,
 	apis: RUNTIME_API_VERSIONS,
 };
@@ -184,6 +187,18 @@ impl template::Trait for Runtime {
 
 impl substratekitties::Trait for Runtime {
   type Event = Event;
+  type BreedingSchedule = substratekitties::DefaultBreedingSchedule;
+  const MUTATION_CHANCE: u8 = 32;
+  type GeneSplicer = substratekitties::MendelianSplicer<Runtime>;
+  type Randomness = substratekitties::SeedWithNonce<Runtime>;
+  const REVEAL_WINDOW: u64 = 20;
+  const COMMITMENT_BOND: u64 = 1 << 30;
+  const MAX_KITTIES_PER_ACCOUNT: u64 = 1_000;
+  const MAX_GEN0_SUPPLY: u64 = 50_000;
+  const PROMO_QUOTA: u64 = 5_000;
+  const MAX_ROYALTY_PARTS: u32 = 100_000;
+  const MAX_OFFER_DURATION: u64 = 201_600;
+  const MAX_AUCTION_DURATION: u64 = 201_600;
 }
 
 construct_runtime!(
@@ -202,7 +217,7 @@ construct_runtime!(
 		Fees: fees::{Module, Storage, Config<T>, Event<T>},
 		// Used for the module template in `./template.rs`
 		TemplateModule: template::{Module, Call, Storage, Event<T>},
-		Substratekitties: substratekitties::{Module, Call, Storage, Event<T>},
+		Substratekitties: substratekitties::{Module, Call, Storage, Event<T>, Config<T>},
 	}
 );
 
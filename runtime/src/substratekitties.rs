@@ -1,21 +1,322 @@
-use parity_codec::Encode;
+use parity_codec::{Decode, Encode, Input};
 use parity_codec_derive::{Decode, Encode};
-use runtime_primitives::traits::{As, Hash, Zero};
+use runtime_primitives::traits::{As, CheckedAdd, CheckedSub, Hash, Zero};
+use runtime_primitives::Permill;
 use support::{
   decl_event, decl_module, decl_storage, dispatch::Result, ensure, StorageMap, StorageValue,
 };
-use system::ensure_signed;
+use rstd::prelude::*;
+use system::{ensure_root, ensure_signed};
 
-#[derive(Encode, Decode, Default, Clone, PartialEq)]
-pub struct Kitty<Hash, Balance> {
+use crate::genetics::{self, Phenotype};
+
+#[cfg(feature = "std")]
+use runtime_io::with_storage;
+
+#[derive(Encode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Kitty<Hash, Balance, BlockNumber> {
   id: Hash,
   dna: Hash,
   price: Balance,
   gen: u64,
+  cooldown_end: BlockNumber,
+  breed_count: u32,
+}
+
+// Kitties stored before cooldowns existed end after `gen`. Rather than migrating every
+// entry up front, they decode with no cooldown and no recorded breedings, and are
+// rewritten in the new layout the next time they are updated.
+impl<Hash: Decode, Balance: Decode, BlockNumber: Decode + Default> Decode for Kitty<Hash, Balance, BlockNumber> {
+  fn decode<I: Input>(input: &mut I) -> Option<Self> {
+    Some(Kitty {
+      id: Decode::decode(input)?,
+      dna: Decode::decode(input)?,
+      price: Decode::decode(input)?,
+      gen: Decode::decode(input)?,
+      cooldown_end: Decode::decode(input).unwrap_or_default(),
+      breed_count: Decode::decode(input).unwrap_or_default(),
+    })
+  }
+}
+
+/// A matron carrying a child that has not been born yet.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Pregnancy<AccountId, Hash, BlockNumber, Balance> {
+  sire: Hash,
+  // The sire's genes are captured at conception, so the child does not depend on
+  // what happens to the sire during gestation
+  sire_dna: Hash,
+  sire_gen: u64,
+  // The matron's owner when it conceived, who receives the child
+  breeder: AccountId,
+  conceived_at: BlockNumber,
+  due_at: BlockNumber,
+  // Randomness for the child's DNA fixed at conception; when `None` it is drawn at birth
+  seed: Option<Hash>,
+  // Creation deposit already reserved from the breeder for the child
+  deposit: Balance,
+}
+
+/// What a commitment will be used for once its secret is revealed.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum CommitmentKind<Hash> {
+  Create,
+  // (matron, sire)
+  Breed(Hash, Hash),
+}
+
+/// The hash of a secret that its account promised to reveal within the reveal window.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Commitment<Hash, BlockNumber, Balance> {
+  kind: CommitmentKind<Hash>,
+  hash: Hash,
+  committed_at: BlockNumber,
+  expires_at: BlockNumber,
+  // Reserved until the secret is revealed, and forfeited if it never is
+  bond: Balance,
+}
+
+/// A kitty on sale for a price that falls linearly from `start_price` to `end_price`
+/// over `duration` blocks, and stays at `end_price` after that.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct DutchAuction<Balance, BlockNumber> {
+  start_price: Balance,
+  end_price: Balance,
+  started_at: BlockNumber,
+  duration: BlockNumber,
+}
+
+/// An ascending auction; the top bid is reserved from the bidder until the auction settles.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Auction<AccountId, Balance, BlockNumber> {
+  // The kitty only sells for at least this much
+  reserve: Balance,
+  end_block: BlockNumber,
+  // (bidder, amount)
+  top_bid: Option<(AccountId, Balance)>,
+}
+
+/// Funds reserved from a buyer for a kitty that is not necessarily for sale.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Offer<Balance, BlockNumber> {
+  amount: Balance,
+  expires_at: BlockNumber,
+}
+
+/// Which side of a swap adds money to the kitties it gives.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum BalanceDelta<Balance> {
+  Nothing,
+  // Reserved from the proposer until the swap is accepted or released
+  ProposerPays(Balance),
+  // Paid by the counterparty when accepting
+  CounterpartyPays(Balance),
+}
+
+impl<Balance> Default for BalanceDelta<Balance> {
+  fn default() -> Self {
+    BalanceDelta::Nothing
+  }
+}
+
+/// A proposed trade of the proposer's kitties for the counterparty's, settled in one go.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Swap<AccountId, Hash, Balance, BlockNumber> {
+  proposer: AccountId,
+  counterparty: AccountId,
+  // Locked until the swap is accepted or released
+  offered: Vec<Hash>,
+  requested: Vec<Hash>,
+  balance_delta: BalanceDelta<Balance>,
+  expires_at: BlockNumber,
+}
+
+/// How the price of a sale is shared out, worked out before any money moves.
+struct PriceSplit<AccountId, Balance> {
+  fee: Balance,
+  // The creator and their cut, if one is owed
+  royalty: Option<(AccountId, Balance)>,
+  proceeds: Balance,
+}
+
+/// The most births `on_finalise` handles in one block; the rest wait for the next one.
+const MAX_BIRTHS_PER_BLOCK: usize = 32;
+
+/// The most commitments `on_finalise` expires in one block; the rest wait for the next one.
+const MAX_COMMITMENT_EXPIRIES_PER_BLOCK: usize = 64;
+
+/// The most auctions `on_finalise` settles in one block; the rest wait for the next one.
+const MAX_SETTLEMENTS_PER_BLOCK: usize = 32;
+
+/// The most offers `on_finalise` expires in one block; the rest wait for the next one.
+const MAX_OFFER_EXPIRIES_PER_BLOCK: usize = 64;
+
+/// The most offers a kitty can have outstanding, which bounds the cost of burning it. Once
+/// a kitty has this many, a new offer must beat the lowest one, which it replaces.
+const MAX_OFFERS_PER_KITTY: usize = 16;
+
+/// The most listings `on_finalise` expires in one block; the rest wait for the next one.
+const MAX_LISTING_EXPIRIES_PER_BLOCK: usize = 64;
+
+/// The most kitties `transfer_batch` moves in one go.
+const MAX_TRANSFER_BATCH: usize = 32;
+
+/// The most kitties both sides of a swap can hold together.
+const MAX_SWAP_KITTIES: usize = 16;
+
+/// The most swaps `on_finalise` expires in one block; the rest wait for the next one.
+const MAX_SWAP_EXPIRIES_PER_BLOCK: usize = 32;
+
+/// Decides how long a kitty has to rest after breeding, and how long a pregnancy lasts.
+pub trait BreedingSchedule<BlockNumber> {
+  /// The number of blocks a parent of generation `gen` cools down for after it has
+  /// been bred `breed_count` times before.
+  fn cooldown(gen: u64, breed_count: u32) -> BlockNumber;
+
+  /// The number of blocks a matron of generation `gen` is pregnant for.
+  fn gestation(gen: u64) -> BlockNumber;
+}
+
+/// No cooldown at all, and children are born in the block they are conceived in.
+impl<BlockNumber: Zero> BreedingSchedule<BlockNumber> for () {
+  fn cooldown(_gen: u64, _breed_count: u32) -> BlockNumber {
+    Zero::zero()
+  }
+
+  fn gestation(_gen: u64) -> BlockNumber {
+    Zero::zero()
+  }
+}
+
+/// Cooldowns in blocks, from fastest to slowest.
+const COOLDOWNS: [u64; 14] = [10, 20, 50, 100, 300, 600, 1_200, 2_400, 4_800, 9_600, 14_400, 28_800, 57_600, 100_800];
+
+/// Starts every kitty half a step slower per generation and moves it one step further
+/// down `COOLDOWNS` each time it breeds, until it reaches the slowest cooldown.
+/// A pregnancy lasts as long as the matron's first cooldown.
+pub struct DefaultBreedingSchedule;
+
+impl<BlockNumber: As<u64>> BreedingSchedule<BlockNumber> for DefaultBreedingSchedule {
+  fn cooldown(gen: u64, breed_count: u32) -> BlockNumber {
+    let step = (gen / 2).saturating_add(u64::from(breed_count));
+    let step = rstd::cmp::min(step, (COOLDOWNS.len() - 1) as u64) as usize;
+    BlockNumber::sa(COOLDOWNS[step])
+  }
+
+  fn gestation(gen: u64) -> BlockNumber {
+    Self::cooldown(gen, 0)
+  }
+}
+
+/// Produces the DNA of a child from the DNA of its parents.
+pub trait GeneSplicer<Hash> {
+  /// The DNA of the child of `matron_dna` and `sire_dna`, drawing any chances from `seed`.
+  fn splice(matron_dna: &Hash, sire_dna: &Hash, seed: &Hash) -> Hash;
+}
+
+/// Takes every byte from the sire when the seed byte at the same position is even, and
+/// from the matron otherwise.
+impl<Hash: Clone + AsRef<[u8]> + AsMut<[u8]>> GeneSplicer<Hash> for () {
+  fn splice(matron_dna: &Hash, sire_dna: &Hash, seed: &Hash) -> Hash {
+    let mut final_dna = matron_dna.clone();
+
+    for (i, (sire_dna_element, r)) in sire_dna.as_ref().iter().zip(seed.as_ref().iter()).enumerate() {
+      if r % 2 == 0 {
+        final_dna.as_mut()[i] = *sire_dna_element;
+      }
+    }
+
+    final_dna
+  }
+}
+
+/// Splices with the dominant and recessive genes of `genetics::splice`, mutating with
+/// the `MUTATION_CHANCE` of `T`.
+pub struct MendelianSplicer<T>(rstd::marker::PhantomData<T>);
+
+impl<T: Trait> GeneSplicer<T::Hash> for MendelianSplicer<T> {
+  fn splice(matron_dna: &T::Hash, sire_dna: &T::Hash, seed: &T::Hash) -> T::Hash {
+    let mut final_dna = matron_dna.clone();
+    genetics::splice(
+      matron_dna.as_ref(),
+      sire_dna.as_ref(),
+      seed.as_ref(),
+      T::MUTATION_CHANCE,
+      final_dna.as_mut(),
+    );
+
+    final_dna
+  }
+}
+
+/// Where kitty ids and DNA get their randomness from.
+pub trait Randomness<Hash> {
+  /// A random hash for `subject`. Every call returns a different hash, even for the
+  /// same subject in the same block.
+  fn random(subject: &[u8]) -> Hash;
+}
+
+/// Hashes the block's random seed with the subject and a nonce that goes up on every call.
+pub struct SeedWithNonce<T>(rstd::marker::PhantomData<T>);
+
+impl<T: Trait> Randomness<T::Hash> for SeedWithNonce<T> {
+  fn random(subject: &[u8]) -> T::Hash {
+    let nonce = <Nonce<T>>::get();
+    <Nonce<T>>::put(nonce.wrapping_add(1));
+
+    (<system::Module<T>>::random_seed(), subject, nonce)
+      .using_encoded(<T as system::Trait>::Hashing::hash)
+  }
 }
 
 pub trait Trait: balances::Trait {
   type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+  /// The chance, out of 256, that a mutation pair of genes actually mutates.
+  const MUTATION_CHANCE: u8;
+
+  /// How the DNA of parents is combined when breeding.
+  type GeneSplicer: GeneSplicer<Self::Hash>;
+
+  /// Where new kitties get their ids and DNA from.
+  type Randomness: Randomness<Self::Hash>;
+
+  /// How many blocks after committing the secret can be revealed; the commitment expires after that.
+  const REVEAL_WINDOW: u64;
+
+  /// What a commitment costs if its secret is never revealed, so that committing again
+  /// until the randomness comes out well is not free.
+  const COMMITMENT_BOND: u64;
+
+  /// How many kitties a single account may own, unless root overrides it.
+  const MAX_KITTIES_PER_ACCOUNT: u64;
+
+  /// How many gen 0 kitties may ever be minted, unless root overrides it.
+  const MAX_GEN0_SUPPLY: u64;
+
+  /// How many kitties root may ever hand out through `mint_promo`.
+  const PROMO_QUOTA: u64;
+
+  /// The highest royalty a creator may ask on resales, in parts per million.
+  const MAX_ROYALTY_PARTS: u32;
+
+  /// How many blocks ahead an offer may expire.
+  const MAX_OFFER_DURATION: u64;
+
+  /// How many blocks ahead an auction may end.
+  const MAX_AUCTION_DURATION: u64;
+
+  /// How long kitties cool down between breedings.
+  type BreedingSchedule: BreedingSchedule<Self::BlockNumber>;
 }
 
 decl_event!(
@@ -23,19 +324,90 @@ decl_event!(
   where
     <T as system::Trait>::AccountId,
     <T as system::Trait>::Hash,
+    <T as system::Trait>::BlockNumber,
     <T as balances::Trait>::Balance
     {
       Created(AccountId, Hash),
       PriceSet(AccountId, Hash, Balance),
       Transferred(AccountId, AccountId, Hash),
       Bought(AccountId, AccountId, Hash, Balance),
+      Burned(AccountId, Hash),
+      Approval(AccountId, AccountId, Hash),
+      ApprovalCleared(AccountId, Hash),
+      ApprovalForAll(AccountId, AccountId, bool),
+      SireAllowed(AccountId, AccountId, Hash),
+      SireListed(AccountId, Hash, Balance),
+      // (breeder, sire owner, matron, sire, fee)
+      SireUsed(AccountId, AccountId, Hash, Hash, Balance),
+      // (owner receiving the child, matron, sire, due block)
+      Conceived(AccountId, Hash, Hash, BlockNumber),
+      // (matron, sire, child)
+      Born(Hash, Hash, Hash),
+      // (matron, owner refunded the creation deposit)
+      PregnancyLost(Hash, AccountId),
+      // (account, commitment, expiry block)
+      Committed(AccountId, Hash, BlockNumber),
+      CommitmentExpired(AccountId, Hash),
+      // (seller, kitty, start price, end price, duration)
+      DutchAuctionCreated(AccountId, Hash, Balance, Balance, BlockNumber),
+      DutchAuctionCancelled(AccountId, Hash),
+      // (seller, kitty, reserve, end block)
+      AuctionCreated(AccountId, Hash, Balance, BlockNumber),
+      AuctionCancelled(AccountId, Hash),
+      BidPlaced(AccountId, Hash, Balance),
+      // (kitty, winner, price); no winner when the auction ended below its reserve
+      AuctionSettled(Hash, Option<AccountId>, Balance),
+      // (buyer, kitty, amount, expiry block)
+      OfferMade(AccountId, Hash, Balance, BlockNumber),
+      // The offer was cancelled, expired or can no longer be accepted; its funds are returned
+      OfferWithdrawn(AccountId, Hash),
+      // (creator, kitty, amount)
+      RoyaltyPaid(AccountId, Hash, Balance),
+      // (kitty, amount)
+      MarketFeeCollected(Hash, Balance),
+      ListingExpired(AccountId, Hash),
+      // (sender, receiver, kitties)
+      BatchTransferred(AccountId, AccountId, Vec<Hash>),
+      // (swap, proposer, counterparty, expiry block)
+      SwapProposed(Hash, AccountId, AccountId, BlockNumber),
+      SwapAccepted(Hash),
+      SwapCancelled(Hash),
+      SwapExpired(Hash),
     }
 );
 
 decl_storage! {
     trait Store for Module<T: Trait> as Kitty {
-        Kitties get(kitty): map T::Hash => Kitty<T::Hash, T::Balance>;
+        Kitties get(kitty): map T::Hash => Kitty<T::Hash, T::Balance, T::BlockNumber>;
         KittyOwner get(owner_of): map T::Hash => Option<T::AccountId>;
+        Approvals get(approved_of): map T::Hash => Option<T::AccountId>;
+        // (owner, operator) => whether `operator` may manage all of `owner`'s kitties
+        OperatorApprovals get(is_approved_for_all): map (T::AccountId, T::AccountId) => bool;
+        // An account other than the owner that may use the kitty as a sire once
+        SireAllowedTo get(sire_allowed_to): map T::Hash => Option<T::AccountId>;
+        // The fee for using the kitty as a sire, zero when it is not offered
+        SirePrice get(sire_price): map T::Hash => T::Balance;
+
+        Pregnancies get(pregnancy_of): map T::Hash => Option<Pregnancy<T::AccountId, T::Hash, T::BlockNumber, T::Balance>>;
+        // Matrons due to give birth at a block
+        PregnanciesDue get(pregnancies_due): map T::BlockNumber => Vec<T::Hash>;
+
+        // (matron, sire) of every bred kitty; gen 0 kitties have none. Pedigrees are
+        // history, so they are kept even after the kitties involved are burned
+        KittyParents get(parents_of): map T::Hash => Option<(T::Hash, T::Hash)>;
+        // Enumerated like owned kitties, so that a busy sire's births do not get costlier
+        KittyChildrenArray get(child_by_index): map (T::Hash, u64) => T::Hash;
+        KittyChildrenCount get(child_count): map T::Hash => u64;
+
+        // Every account has at most one commitment outstanding
+        Commitments get(commitment_of): map T::AccountId => Option<Commitment<T::Hash, T::BlockNumber, T::Balance>>;
+        CommitmentsExpiring get(commitments_expiring): map T::BlockNumber => Vec<T::AccountId>;
+        // Secrets are mixed with the random seed of the block after their commitment, which
+        // nobody knew when committing; it is kept for as long as a reveal can use it
+        RevealSeedNeeded: map T::BlockNumber => bool;
+        RevealSeeds get(reveal_seed_of): map T::BlockNumber => Option<T::Hash>;
+        // Parents held for the account that committed to breeding them
+        ReservedForBreeding get(reserved_for_breeding): map T::Hash => Option<T::AccountId>;
 
         AllKittiesArray get(all_kitties_by_index): map u64 => T::Hash;
         AllKittiesCount get(all_kitties_count): u64;
@@ -45,7 +417,84 @@ decl_storage! {
         OwnedKittiesCount get(owned_kitty_count): map T::AccountId => u64;
         OwnedKittiesIndex: map T::Hash => u64;
 
+        // Only used by `SeedWithNonce`
         Nonce: u64;
+
+        // Gen 0 kitties ever minted, burned ones included
+        Gen0Count get(gen0_count): u64;
+        // Root overrides of the supply caps on `Trait`
+        MaxKittiesPerAccountOverride get(max_kitties_per_account_override): Option<u64>;
+        MaxGen0SupplyOverride get(max_gen0_supply_override): Option<u64>;
+
+        // Reserved from the owner of every newly created or bred kitty
+        CreationDeposit get(creation_deposit) config(): T::Balance;
+        // What the current owner of each kitty has reserved for it
+        KittyDeposit get(deposit_of): map T::Hash => T::Balance;
+
+        // When disabled, new gen 0 kitties only come from `mint_promo`
+        PublicCreationEnabled get(public_creation_enabled) config(): bool = true;
+        // When disabled, kitties are only created and bred through commit and reveal, whose
+        // randomness block authors cannot farm
+        InstantRandomnessEnabled get(instant_randomness_enabled) config(): bool;
+        PromoCount get(promo_count): u64;
+
+        DutchAuctions get(dutch_auction_of): map T::Hash => Option<DutchAuction<T::Balance, T::BlockNumber>>;
+        Auctions get(auction_of): map T::Hash => Option<Auction<T::AccountId, T::Balance, T::BlockNumber>>;
+        AuctionsEnding get(auctions_ending): map T::BlockNumber => Vec<T::Hash>;
+
+        Offers get(offer_of): map (T::Hash, T::AccountId) => Option<Offer<T::Balance, T::BlockNumber>>;
+        // The buyers with an outstanding offer on each kitty
+        OffersFor get(offers_for): map T::Hash => Vec<T::AccountId>;
+        OffersExpiring get(offers_expiring): map T::BlockNumber => Vec<(T::Hash, T::AccountId)>;
+
+        // Whoever a kitty was first minted for, and the share of every resale they ask for
+        KittyCreator get(creator_of): map T::Hash => Option<T::AccountId>;
+        CreatorRoyalty get(royalty_of): map T::AccountId => Permill;
+
+        // Taken from the price of every sale on the marketplace
+        MarketFee get(market_fee) config(): Permill;
+        FeeCollector get(fee_collector) config(): T::AccountId;
+
+        // The block from which a fixed price listing is no longer valid
+        ListingExpiry get(listing_expiry_of): map T::Hash => Option<T::BlockNumber>;
+        ListingsExpiring get(listings_expiring): map T::BlockNumber => Vec<T::Hash>;
+
+        Swaps get(swap_of): map T::Hash => Option<Swap<T::AccountId, T::Hash, T::Balance, T::BlockNumber>>;
+        SwapsExpiring get(swaps_expiring): map T::BlockNumber => Vec<T::Hash>;
+        // The swap each offered kitty is locked in
+        LockedInSwap get(swap_locking): map T::Hash => Option<T::Hash>;
+    }
+
+    add_extra_genesis {
+        // (owner, dna, price, gen) for every kitty that exists from the first block
+        config(kitties): Vec<(T::AccountId, T::Hash, T::Balance, u64)>;
+
+        build(|storage: &mut runtime_primitives::StorageOverlay, _: &mut runtime_primitives::ChildrenStorageOverlay, config: &GenesisConfig<T>| {
+            // The same bound `set_market_fee` enforces, so that sales cannot pay out more than the price
+            assert!(
+                config.market_fee * 1_000_000u64 + T::MAX_ROYALTY_PARTS as u64 <= 1_000_000,
+                "The genesis market fee leaves no room for royalties."
+            );
+
+            with_storage(storage, || {
+                for &(ref owner, dna, price, gen) in &config.kitties {
+                    let mut kitty_dna = dna;
+                    genetics::strip_mutations(kitty_dna.as_mut());
+
+                    let kitty = Kitty {
+                        id: dna,
+                        dna: kitty_dna,
+                        price: price,
+                        gen: gen,
+                        cooldown_end: <T::BlockNumber as As<u64>>::sa(0),
+                        breed_count: 0
+                    };
+
+                    <Module<T>>::_mint(owner.clone(), dna, kitty, Zero::zero())
+                        .unwrap_or_else(|e| panic!("Cannot mint genesis kitty {:?}: {}", dna, e));
+                }
+            });
+        });
     }
 }
 
@@ -54,27 +503,124 @@ decl_module! {
 
         fn deposit_event<T>() = default;
 
+        fn on_finalise(n: T::BlockNumber) {
+            let mut due = <PregnanciesDue<T>>::take(n);
+            if due.len() > MAX_BIRTHS_PER_BLOCK {
+                let postponed = due.split_off(MAX_BIRTHS_PER_BLOCK);
+                <PregnanciesDue<T>>::mutate(n + <T::BlockNumber as As<u64>>::sa(1), |next| next.extend(postponed));
+            }
+
+            for matron_id in due {
+                // The matron may already have given birth through `give_birth`
+                let is_due = Self::pregnancy_of(matron_id).map_or(false, |p| p.due_at <= n);
+                // A child that cannot be minted, say because its owner hit the kitty cap since
+                // conception, is lost rather than leaving the matron pregnant forever
+                if is_due && Self::_give_birth(matron_id).is_err() {
+                    Self::_lose_pregnancy(matron_id);
+                }
+            }
+
+            if <RevealSeedNeeded<T>>::take(n) {
+                <RevealSeeds<T>>::insert(n, <system::Module<T>>::random_seed());
+            }
+            // Commitments that use this seed have all been revealed or expired by now
+            let reveal_window = <T::BlockNumber as As<u64>>::sa(T::REVEAL_WINDOW);
+            if n > reveal_window {
+                <RevealSeeds<T>>::remove(n - reveal_window);
+            }
+
+            let mut expiring = <CommitmentsExpiring<T>>::take(n);
+            if expiring.len() > MAX_COMMITMENT_EXPIRIES_PER_BLOCK {
+                let postponed = expiring.split_off(MAX_COMMITMENT_EXPIRIES_PER_BLOCK);
+                <CommitmentsExpiring<T>>::mutate(n + <T::BlockNumber as As<u64>>::sa(1), |next| next.extend(postponed));
+            }
+
+            for account in expiring {
+                // Skip commitments that were revealed, and maybe replaced, in the meantime
+                let is_expired = Self::commitment_of(&account).map_or(false, |c| c.expires_at <= n);
+                if is_expired {
+                    Self::_expire_commitment(account);
+                }
+            }
+
+            let mut ending = <AuctionsEnding<T>>::take(n);
+            if ending.len() > MAX_SETTLEMENTS_PER_BLOCK {
+                let postponed = ending.split_off(MAX_SETTLEMENTS_PER_BLOCK);
+                <AuctionsEnding<T>>::mutate(n + <T::BlockNumber as As<u64>>::sa(1), |next| next.extend(postponed));
+            }
+
+            for kitty_id in ending {
+                let has_ended = Self::auction_of(kitty_id).map_or(false, |a| a.end_block <= n);
+                if has_ended {
+                    Self::_settle_auction(kitty_id);
+                }
+            }
+
+            let mut expiring = <OffersExpiring<T>>::take(n);
+            if expiring.len() > MAX_OFFER_EXPIRIES_PER_BLOCK {
+                let postponed = expiring.split_off(MAX_OFFER_EXPIRIES_PER_BLOCK);
+                <OffersExpiring<T>>::mutate(n + <T::BlockNumber as As<u64>>::sa(1), |next| next.extend(postponed));
+            }
+
+            for (kitty_id, buyer) in expiring {
+                // The offer may have been withdrawn, and a new one made, in the meantime
+                let is_expired = Self::offer_of((kitty_id, buyer.clone())).map_or(false, |o| o.expires_at <= n);
+                if is_expired {
+                    Self::_withdraw_offer(kitty_id, buyer);
+                }
+            }
+
+            let mut expiring = <ListingsExpiring<T>>::take(n);
+            if expiring.len() > MAX_LISTING_EXPIRIES_PER_BLOCK {
+                let postponed = expiring.split_off(MAX_LISTING_EXPIRIES_PER_BLOCK);
+                <ListingsExpiring<T>>::mutate(n + <T::BlockNumber as As<u64>>::sa(1), |next| next.extend(postponed));
+            }
+
+            for kitty_id in expiring {
+                // The kitty may have been sold or listed again in the meantime
+                let is_expired = Self::listing_expiry_of(kitty_id).map_or(false, |e| e <= n);
+                if is_expired {
+                    Self::_expire_listing(kitty_id);
+                }
+            }
+
+            let mut expiring = <SwapsExpiring<T>>::take(n);
+            if expiring.len() > MAX_SWAP_EXPIRIES_PER_BLOCK {
+                let postponed = expiring.split_off(MAX_SWAP_EXPIRIES_PER_BLOCK);
+                <SwapsExpiring<T>>::mutate(n + <T::BlockNumber as As<u64>>::sa(1), |next| next.extend(postponed));
+            }
+
+            for swap_id in expiring {
+                // The swap may already have been accepted or cancelled
+                if Self::_release_swap(swap_id).is_some() {
+                    Self::deposit_event(RawEvent::SwapExpired(swap_id));
+                }
+            }
+        }
+
         fn create_kitty(origin) -> Result {
             let sender = ensure_signed(origin)?;
+            ensure!(Self::public_creation_enabled(), "Public kitty creation is disabled.");
+            ensure!(Self::instant_randomness_enabled(), "Use commit_create and reveal_create instead.");
 
-            // Nonce and seed a new random hash
-            let nonce = <Nonce<T>>::get();
-            let random_hash = (<system::Module<T>>::random_seed(), &sender, nonce)
-                .using_encoded(<T as system::Trait>::Hashing::hash);
+            let random_hash = T::Randomness::random(&sender.encode());
+
+            // Gen 0 kitties are born without mutations
+            let mut dna = random_hash;
+            genetics::strip_mutations(dna.as_mut());
 
             // New kitty
             let new_kitty = Kitty {
                 id: random_hash,
-                dna: random_hash,
+                dna: dna,
                 price: <T::Balance as As<u64>>::sa(0),
-                gen: 0
+                gen: 0,
+                cooldown_end: <T::BlockNumber as As<u64>>::sa(0),
+                breed_count: 0
             };
 
             // Do the state stuff
-            Self::_mint(sender, random_hash, new_kitty)?;
-
-            // Update nonce
-            <Nonce<T>>::mutate(|n| *n += 1);
+            Self::_create(sender, random_hash, new_kitty)?;
 
             Ok(())
         }
@@ -85,11 +631,9 @@ decl_module! {
             // Check that the kitty with `kitty_id` exists
             ensure!(<Kitties<T>>::exists(kitty_id), "This kitty does not exist.");
 
-            // Check if owner exists for `kitty_id`
-            //      - If it does, check that `sender` is the `owner`
-            //      - If it doesn't, return an `Err()` that no `owner` exists
-            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty.")?;
-            ensure!(owner == sender, "You do not own this kitty.");
+            // Check that `sender` is the `owner` of `kitty_id` or one of their operators
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
 
             let mut kitty = Self::kitty(kitty_id);
 
@@ -98,6 +642,7 @@ decl_module! {
 
             // Update the kitty in storage
             <Kitties<T>>::insert(kitty_id, kitty);
+            <ListingExpiry<T>>::remove(kitty_id);
 
             // Deposit a `PriceSet` event with relevant data
             //      - owner
@@ -108,13 +653,137 @@ decl_module! {
             Ok(())
         }
 
+        fn set_price_with_expiry(origin, kitty_id: T::Hash, new_price: T::Balance, expiry: T::BlockNumber) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(<Kitties<T>>::exists(kitty_id), "This kitty does not exist.");
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+
+            ensure!(!new_price.is_zero(), "A listing that expires needs a price.");
+            ensure!(expiry > <system::Module<T>>::block_number(), "The listing must expire in a later block.");
+
+            let mut kitty = Self::kitty(kitty_id);
+            kitty.price = new_price;
+            <Kitties<T>>::insert(kitty_id, kitty);
+
+            <ListingExpiry<T>>::insert(kitty_id, expiry);
+            <ListingsExpiring<T>>::mutate(expiry, |expiring| expiring.push(kitty_id));
+
+            Self::deposit_event(RawEvent::PriceSet(owner, kitty_id, new_price));
+
+            Ok(())
+        }
+
         fn transfer(origin, to: T::AccountId, kitty_id: T::Hash) -> Result {
             let sender = ensure_signed(origin)?;
 
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+
+            Self::_transfer_from(owner, to, kitty_id)?;
+
+            Ok(())
+        }
+
+        fn transfer_batch(origin, to: T::AccountId, kitty_ids: Vec<T::Hash>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(!kitty_ids.is_empty(), "There are no kitties to transfer.");
+            ensure!(kitty_ids.len() <= MAX_TRANSFER_BATCH, "Too many kitties to transfer at once.");
+
+            // Check everything up front, so that either all kitties move or none do
+            let mut owners = Vec::with_capacity(kitty_ids.len());
+            let mut received: u64 = 0;
+            let mut deposits = <T::Balance as As<u64>>::sa(0);
+            for (i, &kitty_id) in kitty_ids.iter().enumerate() {
+                ensure!(!kitty_ids[..i].contains(&kitty_id), "A kitty appears twice in the batch.");
+
+                let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+                ensure!(owner != to, "You cannot transfer a kitty to its owner.");
+                Self::_ensure_unlocked(kitty_id)?;
+
+                received += 1;
+                deposits = deposits.checked_add(&Self::deposit_of(kitty_id))
+                    .ok_or("Overflow when adding up the creation deposits.")?;
+                owners.push(owner);
+            }
+
+            let new_owned_kitty_count_to = Self::owned_kitty_count(&to)
+                .checked_add(received)
+                .ok_or("Over 18 quintillion!!! Too many kitties!")?;
+            ensure!(
+                new_owned_kitty_count_to <= Self::max_kitties_per_account(),
+                "The receiver owns too many kitties."
+            );
+            ensure!(
+                <balances::Module<T>>::free_balance(&to) >= deposits,
+                "The receiver cannot afford the creation deposits."
+            );
+
+            for (owner, &kitty_id) in owners.into_iter().zip(kitty_ids.iter()) {
+                Self::_move_kitty(owner, to.clone(), kitty_id)?;
+            }
+
+            Self::deposit_event(RawEvent::BatchTransferred(sender, to, kitty_ids));
+
+            Ok(())
+        }
+
+        fn approve(origin, kitty_id: T::Hash, spender: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            ensure!(spender != owner, "You cannot approve yourself.");
+
+            <Approvals<T>>::insert(kitty_id, &spender);
+
+            Self::deposit_event(RawEvent::Approval(owner, spender, kitty_id));
+
+            Ok(())
+        }
+
+        fn clear_approval(origin, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            ensure!(<Approvals<T>>::exists(kitty_id), "This kitty has no approved account.");
+
+            <Approvals<T>>::remove(kitty_id);
+
+            Self::deposit_event(RawEvent::ApprovalCleared(owner, kitty_id));
+
+            Ok(())
+        }
+
+        fn transfer_from(origin, from: T::AccountId, to: T::AccountId, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+
             let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty.")?;
-            ensure!(owner == sender, "You do not own this kitty.");
+            ensure!(owner == from, "The 'from' account does not own this kitty.");
+            ensure!(
+                sender == owner
+                    || Self::is_approved_for_all((owner.clone(), sender.clone()))
+                    || Self::approved_of(kitty_id) == Some(sender),
+                "You are not allowed to transfer this kitty."
+            );
+
+            Self::_transfer_from(from, to, kitty_id)?;
+
+            Ok(())
+        }
 
-            Self::_transfer_from(sender, to, kitty_id)?;
+        fn set_approval_for_all(origin, operator: T::AccountId, approved: bool) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(operator != sender, "You cannot make yourself an operator.");
+
+            if approved {
+                <OperatorApprovals<T>>::insert((sender.clone(), operator.clone()), true);
+            } else {
+                <OperatorApprovals<T>>::remove((sender.clone(), operator.clone()));
+            }
+
+            Self::deposit_event(RawEvent::ApprovalForAll(sender, operator, approved));
 
             Ok(())
         }
@@ -130,6 +799,7 @@ decl_module! {
             
             // ACTION: Check that the `sender` is not the `owner`
             ensure!(owner != buyer, "You cannot buy your own kitty.");
+            Self::_ensure_unlocked(kitty_id)?;
 
             let mut kitty = Self::kitty(kitty_id);
 
@@ -137,19 +807,23 @@ decl_module! {
             //      HINT:  `runtime_primitives::traits::Zero` allows you to call `kitty_price.is_zero()` which returns a bool
             let kitty_price = kitty.price;
             ensure!(!kitty_price.is_zero(), "The kitty is not for sale.");
+            if let Some(expiry) = Self::listing_expiry_of(kitty_id) {
+                ensure!(<system::Module<T>>::block_number() < expiry, "This listing has expired.");
+            }
 
             // ACTION: Check `kitty_price` is less than or equal to max_price
             ensure!(kitty_price <= max_price, "The kitty costs more than the price you offer.");
 
-            // ACTION: Use the `Balances` module's `make_transfer()` function to safely transfer funds
-            <balances::Module<T>>::make_transfer(&buyer, &owner, kitty_price)?;
+            Self::_ensure_can_buy(&buyer, kitty_id, kitty_price)?;
+            let split = Self::_split_price(&owner, kitty_id, kitty_price)?;
 
-            // ACTION: Transfer the kitty
-             Self::_transfer_from(owner.clone(), buyer.clone(), kitty_id)?;
+            // Pay for and transfer the kitty
+            Self::_sell(owner.clone(), buyer.clone(), kitty_id, kitty_price, split)?;
 
             // ACTION: Reset kitty price back to zero, and update the storage
             kitty.price = <T::Balance as As<u64>>::sa(0);
             <Kitties<T>>::insert(kitty_id, kitty);
+            <ListingExpiry<T>>::remove(kitty_id);
 
             // ACTION: Create an event for the cat being bought with relevant details
             //      - new owner
@@ -162,101 +836,1265 @@ decl_module! {
             Ok(())
         }
 
-        fn breed_kitty(origin, kitty_id_1: T::Hash, kitty_id_2: T::Hash) -> Result{
+        fn create_dutch_auction(origin, kitty_id: T::Hash, start_price: T::Balance, end_price: T::Balance, duration_blocks: T::BlockNumber) -> Result {
             let sender = ensure_signed(origin)?;
 
-            // ACTION: Check both kitty 1 and kitty 2 "exists"
-             ensure!(<Kitties<T>>::exists(kitty_id_1), "Kitty 1 does not exist.");
-             ensure!(<Kitties<T>>::exists(kitty_id_2), "Kitty 2 does not exist.");
+            ensure!(<Kitties<T>>::exists(kitty_id), "This kitty does not exist.");
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+
+            ensure!(start_price >= end_price, "The price of a dutch auction cannot rise.");
+            ensure!(!duration_blocks.is_zero(), "The auction must last at least one block.");
 
-            // ACTION: Generate a `random_hash` using the <Nonce<T>>
-            // Nonce and seed a new random hash
-            let nonce = <Nonce<T>>::get();
-            let random_hash = (<system::Module<T>>::random_seed(), &sender, nonce)
-                .using_encoded(<T as system::Trait>::Hashing::hash);
+            let auction = DutchAuction {
+                start_price: start_price,
+                end_price: end_price,
+                started_at: <system::Module<T>>::block_number(),
+                duration: duration_blocks,
+            };
 
-            let kitty_1 = Self::kitty(kitty_id_1);
-            let kitty_2 = Self::kitty(kitty_id_2);
+            // A fixed price listing would compete with the auction
+            let mut kitty = Self::kitty(kitty_id);
+            kitty.price = <T::Balance as As<u64>>::sa(0);
+            <Kitties<T>>::insert(kitty_id, kitty);
+            <ListingExpiry<T>>::remove(kitty_id);
 
-            // Our gene splicing algorithm, feel free to make it your own
-            let mut final_dna = kitty_1.dna;
+            <DutchAuctions<T>>::insert(kitty_id, auction);
 
-            for (i, (dna_2_element, r)) in kitty_2.dna.as_ref().iter().zip(random_hash.as_ref().iter()).enumerate() {
-                if r % 2 == 0 {
-                    final_dna.as_mut()[i] = *dna_2_element;
-                }
-            }
+            Self::deposit_event(RawEvent::DutchAuctionCreated(owner, kitty_id, start_price, end_price, duration_blocks));
 
-            // ACTION: Create a `new_kitty` using: 
-            //      - `random_hash` as `id`
-            //      - `final_dna` as `dna`
-            //      - 0 as `price`
-            //      - the max of the parent's `gen` + 1
-            //          - Hint: `rstd::cmp::max(1, 5) + 1` is `6`
+            Ok(())
+        }
 
-            // New kitty
-            let new_kitty = Kitty {
-                id: random_hash,
-                dna: final_dna,
-                price: <T::Balance as As<u64>>::sa(0),
-                gen: rstd::cmp::max(kitty_1.gen, kitty_2.gen) + 1
-            };
+        fn bid_dutch(origin, kitty_id: T::Hash, max_price: T::Balance) -> Result {
+            let buyer = ensure_signed(origin)?;
 
-            // ACTION: `_mint()` your new kitty
-            // Do the state stuff
-            Self::_mint(sender, random_hash, new_kitty)?;
+            let auction = Self::dutch_auction_of(kitty_id).ok_or("This kitty is not on auction.")?;
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty.")?;
+            ensure!(owner != buyer, "You cannot buy your own kitty.");
+
+            let price = Self::_dutch_price(&auction, <system::Module<T>>::block_number());
+            ensure!(price <= max_price, "The kitty costs more than the price you offer.");
+
+            Self::_ensure_can_buy(&buyer, kitty_id, price)?;
+            let split = Self::_split_price(&owner, kitty_id, price)?;
 
-            // ACTION: Update the <Nonce<T>>
-            <Nonce<T>>::mutate(|n| *n += 1);
+            <DutchAuctions<T>>::remove(kitty_id);
+            Self::_sell(owner.clone(), buyer.clone(), kitty_id, price, split)?;
+
+            Self::deposit_event(RawEvent::Bought(buyer, owner, kitty_id, price));
 
             Ok(())
         }
-    }
-}
 
-impl<T: Trait> Module<T> {
-  fn _mint(to: T::AccountId, kitty_id: T::Hash, new_kitty: Kitty<T::Hash, T::Balance>) -> Result {
-    ensure!(!<Kitties<T>>::exists(kitty_id), "The kitty already exists");
+        fn cancel_dutch_auction(origin, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
 
-    let owned_kitty_count = Self::owned_kitty_count(&to);
-    let new_owned_kitty_count = owned_kitty_count
-      .checked_add(1)
-      .ok_or("Over 18 quintillion for the user!!! Too many kitties!")?;
+            ensure!(<DutchAuctions<T>>::exists(kitty_id), "This kitty is not on auction.");
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
 
-    let all_kitties_count = Self::all_kitties_count();
-    let new_all_kitties_count = all_kitties_count
-      .checked_add(1)
-      .ok_or("Over 18 quintillion!!! Too many kitties!")?;
+            <DutchAuctions<T>>::remove(kitty_id);
 
-    // Update new kitty store
-    <Kitties<T>>::insert(kitty_id, new_kitty);
-    <KittyOwner<T>>::insert(kitty_id, &to);
+            Self::deposit_event(RawEvent::DutchAuctionCancelled(owner, kitty_id));
 
-    // Update global kitties tracking
-    <AllKittiesArray<T>>::insert(all_kitties_count, kitty_id);
-    <AllKittiesIndex<T>>::insert(kitty_id, all_kitties_count);
-    <AllKittiesCount<T>>::put(new_all_kitties_count);
+            Ok(())
+        }
 
-    <OwnedKittiesArray<T>>::insert((to.clone(), owned_kitty_count), kitty_id);
-    <OwnedKittiesIndex<T>>::insert(kitty_id, owned_kitty_count);
-    <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count);
+        fn create_auction(origin, kitty_id: T::Hash, reserve: T::Balance, end_block: T::BlockNumber) -> Result {
+            let sender = ensure_signed(origin)?;
 
-    Self::deposit_event(RawEvent::Created(to, kitty_id));
+            ensure!(<Kitties<T>>::exists(kitty_id), "This kitty does not exist.");
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+
+            let now = <system::Module<T>>::block_number();
+            ensure!(end_block > now, "The auction must end in a later block.");
+            ensure!(
+                end_block <= now + <T::BlockNumber as As<u64>>::sa(T::MAX_AUCTION_DURATION),
+                "The auction ends too far in the future."
+            );
+
+            let auction = Auction {
+                reserve: reserve,
+                end_block: end_block,
+                top_bid: None,
+            };
 
-    Ok(())
-  }
+            // A fixed price listing would compete with the auction
+            let mut kitty = Self::kitty(kitty_id);
+            kitty.price = <T::Balance as As<u64>>::sa(0);
+            <Kitties<T>>::insert(kitty_id, kitty);
+            <ListingExpiry<T>>::remove(kitty_id);
 
-  fn _transfer_from(from: T::AccountId, to: T::AccountId, kitty_id: T::Hash) -> Result {
-    // Check if owner exists for `kitty_id`
-    //      - If it does, sanity check that `from` is the `owner`
-    //      - If it doesn't, return an `Err()` that no `owner` exists
-    let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty.")?;
-    ensure!(owner == from, "The 'from' account does not own this kitty.");
+            <Auctions<T>>::insert(kitty_id, auction);
+            <AuctionsEnding<T>>::mutate(end_block, |ending| ending.push(kitty_id));
 
-    let owned_kitty_count_from = Self::owned_kitty_count(&from);
-    let owned_kitty_count_to = Self::owned_kitty_count(&to);
+            Self::deposit_event(RawEvent::AuctionCreated(owner, kitty_id, reserve, end_block));
 
-    // Used `checked_add()` to increment the `owned_kitty_count_to` by one into `new_owned_kitty_count_to`
+            Ok(())
+        }
+
+        fn cancel_auction(origin, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let auction = Self::auction_of(kitty_id).ok_or("This kitty is not on auction.")?;
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            // Bidders are promised the auction runs its course
+            ensure!(auction.top_bid.is_none(), "The auction already has bids.");
+
+            <Auctions<T>>::remove(kitty_id);
+
+            Self::deposit_event(RawEvent::AuctionCancelled(owner, kitty_id));
+
+            Ok(())
+        }
+
+        fn bid(origin, kitty_id: T::Hash, amount: T::Balance) -> Result {
+            let bidder = ensure_signed(origin)?;
+
+            let mut auction = Self::auction_of(kitty_id).ok_or("This kitty is not on auction.")?;
+            ensure!(<system::Module<T>>::block_number() < auction.end_block, "The auction has ended.");
+
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty.")?;
+            ensure!(owner != bidder, "You cannot bid on your own kitty.");
+
+            let previous = auction.top_bid.take();
+            if let Some((_, top_amount)) = previous {
+                ensure!(amount > top_amount, "Your bid must be higher than the top bid.");
+            }
+
+            <balances::Module<T>>::reserve(&bidder, amount).map_err(|_| "You cannot afford this bid.")?;
+            if let Some((previous_bidder, previous_amount)) = previous {
+                <balances::Module<T>>::unreserve(&previous_bidder, previous_amount);
+            }
+
+            auction.top_bid = Some((bidder.clone(), amount));
+            <Auctions<T>>::insert(kitty_id, auction);
+
+            Self::deposit_event(RawEvent::BidPlaced(bidder, kitty_id, amount));
+
+            Ok(())
+        }
+
+        fn make_offer(origin, kitty_id: T::Hash, amount: T::Balance, expiry: T::BlockNumber) -> Result {
+            let buyer = ensure_signed(origin)?;
+
+            let owner = Self::owner_of(kitty_id).ok_or("This kitty does not exist.")?;
+            ensure!(owner != buyer, "You cannot make an offer on your own kitty.");
+            ensure!(!amount.is_zero(), "The offer must be more than zero.");
+            let now = <system::Module<T>>::block_number();
+            ensure!(expiry > now, "The offer must expire in a later block.");
+            ensure!(
+                expiry <= now + <T::BlockNumber as As<u64>>::sa(T::MAX_OFFER_DURATION),
+                "The offer expires too far in the future."
+            );
+
+            ensure!(!<Offers<T>>::exists((kitty_id, buyer.clone())), "You already made an offer on this kitty.");
+            let outbid = if Self::offers_for(kitty_id).len() < MAX_OFFERS_PER_KITTY {
+                None
+            } else {
+                let lowest = Self::offers_for(kitty_id)
+                    .into_iter()
+                    .filter_map(|b| Self::offer_of((kitty_id, b.clone())).map(|offer| (b, offer.amount)))
+                    .min_by_key(|offer| offer.1);
+                if let Some((_, lowest_amount)) = lowest {
+                    ensure!(amount > lowest_amount, "Your offer is lower than every other offer on this kitty.");
+                }
+                lowest.map(|offer| offer.0)
+            };
+
+            <balances::Module<T>>::reserve(&buyer, amount).map_err(|_| "You cannot afford this offer.")?;
+            if let Some(lowest_buyer) = outbid {
+                Self::_withdraw_offer(kitty_id, lowest_buyer);
+            }
+            let mut buyers = Self::offers_for(kitty_id);
+
+            let offer = Offer {
+                amount: amount,
+                expires_at: expiry,
+            };
+
+            buyers.push(buyer.clone());
+            <OffersFor<T>>::insert(kitty_id, buyers);
+            <Offers<T>>::insert((kitty_id, buyer.clone()), offer);
+            <OffersExpiring<T>>::mutate(expiry, |expiring| expiring.push((kitty_id, buyer.clone())));
+
+            Self::deposit_event(RawEvent::OfferMade(buyer, kitty_id, amount, expiry));
+
+            Ok(())
+        }
+
+        fn accept_offer(origin, kitty_id: T::Hash, buyer: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(<Kitties<T>>::exists(kitty_id), "This kitty does not exist.");
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+
+            let offer = Self::offer_of((kitty_id, buyer.clone())).ok_or("There is no such offer.")?;
+            ensure!(<system::Module<T>>::block_number() < offer.expires_at, "This offer has expired.");
+
+            // Nothing may fail once the money starts moving
+            Self::_ensure_can_receive(&buyer, kitty_id)?;
+            let split = Self::_split_price(&owner, kitty_id, offer.amount)?;
+
+            Self::_remove_offer(kitty_id, &buyer);
+            Self::_pay_reserved(&buyer, &owner, kitty_id, split);
+            Self::_transfer_from(owner.clone(), buyer.clone(), kitty_id)?;
+
+            Self::deposit_event(RawEvent::Bought(buyer, owner, kitty_id, offer.amount));
+
+            Ok(())
+        }
+
+        fn cancel_offer(origin, kitty_id: T::Hash) -> Result {
+            let buyer = ensure_signed(origin)?;
+
+            ensure!(<Offers<T>>::exists((kitty_id, buyer.clone())), "There is no such offer.");
+
+            Self::_withdraw_offer(kitty_id, buyer);
+
+            Ok(())
+        }
+
+        fn reject_offer(origin, kitty_id: T::Hash, buyer: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(<Kitties<T>>::exists(kitty_id), "This kitty does not exist.");
+            Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            ensure!(<Offers<T>>::exists((kitty_id, buyer.clone())), "There is no such offer.");
+
+            Self::_withdraw_offer(kitty_id, buyer);
+
+            Ok(())
+        }
+
+        fn propose_swap(
+            origin,
+            my_kitties: Vec<T::Hash>,
+            their_kitties: Vec<T::Hash>,
+            balance_delta: BalanceDelta<T::Balance>,
+            counterparty: T::AccountId,
+            expiry: T::BlockNumber
+        ) -> Result {
+            let proposer = ensure_signed(origin)?;
+
+            ensure!(proposer != counterparty, "You cannot swap with yourself.");
+            ensure!(!my_kitties.is_empty() || !their_kitties.is_empty(), "The swap has no kitties.");
+            ensure!(my_kitties.len() + their_kitties.len() <= MAX_SWAP_KITTIES, "The swap has too many kitties.");
+            ensure!(expiry > <system::Module<T>>::block_number(), "The swap must expire in a later block.");
+
+            for (i, &kitty_id) in my_kitties.iter().enumerate() {
+                ensure!(!my_kitties[..i].contains(&kitty_id), "A kitty appears twice in the swap.");
+                ensure!(Self::owner_of(kitty_id) == Some(proposer.clone()), "You do not own this kitty.");
+                Self::_ensure_unlocked(kitty_id)?;
+            }
+            for (i, &kitty_id) in their_kitties.iter().enumerate() {
+                ensure!(!their_kitties[..i].contains(&kitty_id), "A kitty appears twice in the swap.");
+                ensure!(
+                    Self::owner_of(kitty_id) == Some(counterparty.clone()),
+                    "The counterparty does not own this kitty."
+                );
+            }
+
+            if let BalanceDelta::ProposerPays(amount) = balance_delta {
+                <balances::Module<T>>::reserve(&proposer, amount).map_err(|_| "You cannot afford this swap.")?;
+            }
+
+            let swap_id = T::Randomness::random(&proposer.encode());
+            for &kitty_id in my_kitties.iter() {
+                <LockedInSwap<T>>::insert(kitty_id, swap_id);
+
+                // A fixed price listing would compete with the swap
+                let mut kitty = Self::kitty(kitty_id);
+                kitty.price = <T::Balance as As<u64>>::sa(0);
+                <Kitties<T>>::insert(kitty_id, kitty);
+                <ListingExpiry<T>>::remove(kitty_id);
+            }
+
+            let swap = Swap {
+                proposer: proposer.clone(),
+                counterparty: counterparty.clone(),
+                offered: my_kitties,
+                requested: their_kitties,
+                balance_delta: balance_delta,
+                expires_at: expiry,
+            };
+
+            <Swaps<T>>::insert(swap_id, swap);
+            <SwapsExpiring<T>>::mutate(expiry, |expiring| expiring.push(swap_id));
+
+            Self::deposit_event(RawEvent::SwapProposed(swap_id, proposer, counterparty, expiry));
+
+            Ok(())
+        }
+
+        fn accept_swap(origin, swap_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let swap = Self::swap_of(swap_id).ok_or("This swap does not exist.")?;
+            ensure!(sender == swap.counterparty, "This swap is not for you.");
+            ensure!(<system::Module<T>>::block_number() < swap.expires_at, "This swap has expired.");
+
+            let proposer = swap.proposer.clone();
+            let counterparty = swap.counterparty.clone();
+
+            // Check everything up front, so that either the whole swap happens or nothing does
+            for &kitty_id in swap.requested.iter() {
+                ensure!(
+                    Self::owner_of(kitty_id) == Some(counterparty.clone()),
+                    "You no longer own a kitty in this swap."
+                );
+                Self::_ensure_unlocked(kitty_id)?;
+            }
+
+            let offered = swap.offered.len() as u64;
+            let requested = swap.requested.len() as u64;
+            ensure!(
+                Self::owned_kitty_count(&proposer) - offered + requested <= Self::max_kitties_per_account(),
+                "The proposer would own too many kitties."
+            );
+            ensure!(
+                Self::owned_kitty_count(&counterparty) - requested + offered <= Self::max_kitties_per_account(),
+                "You would own too many kitties."
+            );
+
+            let mut proposer_deposits = <T::Balance as As<u64>>::sa(0);
+            for &kitty_id in swap.requested.iter() {
+                proposer_deposits = proposer_deposits.checked_add(&Self::deposit_of(kitty_id))
+                    .ok_or("Overflow when adding up the creation deposits.")?;
+            }
+            let mut counterparty_cost = match swap.balance_delta {
+                BalanceDelta::CounterpartyPays(amount) => amount,
+                _ => <T::Balance as As<u64>>::sa(0),
+            };
+            for &kitty_id in swap.offered.iter() {
+                counterparty_cost = counterparty_cost.checked_add(&Self::deposit_of(kitty_id))
+                    .ok_or("Overflow when adding up the creation deposits.")?;
+            }
+            ensure!(
+                <balances::Module<T>>::free_balance(&proposer) >= proposer_deposits,
+                "The proposer cannot afford the creation deposits."
+            );
+            ensure!(
+                <balances::Module<T>>::free_balance(&counterparty) >= counterparty_cost,
+                "You cannot afford this swap."
+            );
+            // `repatriate_reserved` only pays into accounts that exist
+            let payee = match swap.balance_delta {
+                BalanceDelta::ProposerPays(_) => Some(&counterparty),
+                BalanceDelta::CounterpartyPays(_) => Some(&proposer),
+                BalanceDelta::Nothing => None,
+            };
+            if let Some(payee) = payee {
+                ensure!(
+                    !<balances::Module<T>>::total_balance(payee).is_zero(),
+                    "The account to be paid does not exist."
+                );
+            }
+
+            // Held back like the proposer's payment until the kitties have moved
+            if let BalanceDelta::CounterpartyPays(amount) = swap.balance_delta {
+                <balances::Module<T>>::reserve(&counterparty, amount).map_err(|_| "You cannot afford this swap.")?;
+            }
+
+            // None of these moves can fail: everything `_move_kitty` checks was checked above, and
+            // it leaves the kitty cap to the final counts, so the order the kitties move in does not matter
+            for &kitty_id in swap.offered.iter() {
+                <LockedInSwap<T>>::remove(kitty_id);
+                let _ = Self::_move_kitty(proposer.clone(), counterparty.clone(), kitty_id);
+            }
+            for &kitty_id in swap.requested.iter() {
+                let _ = Self::_move_kitty(counterparty.clone(), proposer.clone(), kitty_id);
+            }
+            <Swaps<T>>::remove(swap_id);
+
+            match swap.balance_delta {
+                BalanceDelta::ProposerPays(amount) => {
+                    let _ = <balances::Module<T>>::repatriate_reserved(&proposer, &counterparty, amount);
+                },
+                BalanceDelta::CounterpartyPays(amount) => {
+                    let _ = <balances::Module<T>>::repatriate_reserved(&counterparty, &proposer, amount);
+                },
+                BalanceDelta::Nothing => {},
+            }
+
+            Self::deposit_event(RawEvent::SwapAccepted(swap_id));
+
+            Ok(())
+        }
+
+        fn cancel_swap(origin, swap_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let swap = Self::swap_of(swap_id).ok_or("This swap does not exist.")?;
+            ensure!(sender == swap.proposer, "You did not propose this swap.");
+
+            Self::_release_swap(swap_id);
+
+            Self::deposit_event(RawEvent::SwapCancelled(swap_id));
+
+            Ok(())
+        }
+
+        fn breed_kitty(origin, kitty_id_1: T::Hash, kitty_id_2: T::Hash) -> Result{
+            let sender = ensure_signed(origin)?;
+            ensure!(Self::instant_randomness_enabled(), "Use commit_breed and reveal_breed instead.");
+
+            // Kitty 1 is the matron and kitty 2 the sire
+            let (matron_owner, controls_sire) = Self::_ensure_may_breed(&sender, kitty_id_1, kitty_id_2)?;
+
+            // The child belongs to the matron's owner, even when an operator breeds it
+            let deposit = Self::_reserve_creation_deposit(&matron_owner)?;
+            Self::_conceive(matron_owner, kitty_id_1, kitty_id_2, None, deposit)?;
+
+            // A sire allowance is good for a single breeding
+            if !controls_sire {
+                <SireAllowedTo<T>>::remove(kitty_id_2);
+            }
+
+            Ok(())
+        }
+
+        fn commit_create(origin, commitment: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(Self::public_creation_enabled(), "Public kitty creation is disabled.");
+
+            Self::_commit(sender, CommitmentKind::Create, commitment)?;
+
+            Ok(())
+        }
+
+        fn reveal_create(origin, secret: T::Hash) -> Result {
+            // Creation was allowed when committing; disabling it since must not cost the bond
+            let sender = ensure_signed(origin)?;
+
+            let (commitment, mut dna) = Self::_reveal(&sender, secret)?;
+            ensure!(commitment.kind == CommitmentKind::Create, "You have no creation commitment.");
+
+            let kitty_id = T::Randomness::random(&sender.encode());
+            genetics::strip_mutations(dna.as_mut());
+
+            let new_kitty = Kitty {
+                id: kitty_id,
+                dna: dna,
+                price: <T::Balance as As<u64>>::sa(0),
+                gen: 0,
+                cooldown_end: <T::BlockNumber as As<u64>>::sa(0),
+                breed_count: 0
+            };
+
+            Self::_create(sender.clone(), kitty_id, new_kitty)?;
+
+            <Commitments<T>>::remove(&sender);
+            <balances::Module<T>>::unreserve(&sender, commitment.bond);
+
+            Ok(())
+        }
+
+        fn commit_breed(origin, matron_id: T::Hash, sire_id: T::Hash, commitment: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let (_, controls_sire) = Self::_ensure_may_breed(&sender, matron_id, sire_id)?;
+
+            Self::_commit(sender.clone(), CommitmentKind::Breed(matron_id, sire_id), commitment)?;
+
+            // Hold on to both parents until the secret is revealed or the commitment expires
+            <ReservedForBreeding<T>>::insert(matron_id, &sender);
+            <ReservedForBreeding<T>>::insert(sire_id, &sender);
+
+            if !controls_sire {
+                <SireAllowedTo<T>>::remove(sire_id);
+            }
+
+            Ok(())
+        }
+
+        fn reveal_breed(origin, secret: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let (commitment, seed) = Self::_reveal(&sender, secret)?;
+            let (matron_id, sire_id) = match commitment.kind {
+                CommitmentKind::Breed(matron_id, sire_id) => (matron_id, sire_id),
+                _ => return Err("You have no breeding commitment."),
+            };
+
+            // The matron is reserved, so it still has the owner it had when committing
+            let matron_owner = Self::owner_of(matron_id).ok_or("No owner for the matron.")?;
+            Self::_ensure_can_take_child(&matron_owner)?;
+            let deposit = Self::_reserve_creation_deposit(&matron_owner)?;
+
+            <ReservedForBreeding<T>>::remove(matron_id);
+            <ReservedForBreeding<T>>::remove(sire_id);
+            <Commitments<T>>::remove(&sender);
+            <balances::Module<T>>::unreserve(&sender, commitment.bond);
+
+            Self::_conceive(matron_owner, matron_id, sire_id, Some(seed), deposit)?;
+
+            Ok(())
+        }
+
+        fn give_birth(origin, matron_id: T::Hash) -> Result {
+            let _ = ensure_signed(origin)?;
+
+            let pregnancy = Self::pregnancy_of(matron_id).ok_or("This kitty is not pregnant.")?;
+            ensure!(
+                pregnancy.due_at <= <system::Module<T>>::block_number(),
+                "The matron is not ready to give birth yet."
+            );
+
+            Self::_give_birth(matron_id)?;
+
+            Ok(())
+        }
+
+        fn allow_sire(origin, kitty_id: T::Hash, account: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+            ensure!(account != owner, "The owner can always breed with their own kitty.");
+
+            <SireAllowedTo<T>>::insert(kitty_id, &account);
+
+            Self::deposit_event(RawEvent::SireAllowed(owner, account, kitty_id));
+
+            Ok(())
+        }
+
+        fn set_sire_price(origin, kitty_id: T::Hash, price: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let owner = Self::_ensure_owner_or_operator(&sender, kitty_id)?;
+
+            // A zero price takes the kitty off the siring market
+            if price.is_zero() {
+                <SirePrice<T>>::remove(kitty_id);
+            } else {
+                <SirePrice<T>>::insert(kitty_id, price);
+            }
+
+            Self::deposit_event(RawEvent::SireListed(owner, kitty_id, price));
+
+            Ok(())
+        }
+
+        fn breed_with_sire(origin, matron_id: T::Hash, sire_id: T::Hash, max_fee: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(Self::instant_randomness_enabled(), "Use commit_breed and reveal_breed instead.");
+
+            ensure!(<Kitties<T>>::exists(matron_id), "The matron does not exist.");
+            ensure!(<Kitties<T>>::exists(sire_id), "The sire does not exist.");
+            ensure!(matron_id != sire_id, "A kitty cannot breed with itself.");
+
+            let matron_owner = Self::owner_of(matron_id).ok_or("No owner for the matron.")?;
+            ensure!(Self::_is_owner_or_operator(&sender, &matron_owner), "You do not own the matron.");
+
+            let sire_owner = Self::owner_of(sire_id).ok_or("No owner for the sire.")?;
+            ensure!(sire_owner != sender, "You cannot pay yourself for your own sire.");
+
+            let fee = Self::sire_price(sire_id);
+            ensure!(!fee.is_zero(), "The sire is not available for siring.");
+            ensure!(fee <= max_fee, "The sire costs more than the fee you offer.");
+
+            Self::_ensure_not_related(matron_id, sire_id)?;
+            Self::_ensure_ready_to_breed(matron_id, sire_id)?;
+
+            // The sender pays for the service, but the child belongs to the matron's owner
+            Self::_ensure_can_take_child(&matron_owner)?;
+            let deposit = Self::_reserve_creation_deposit(&matron_owner)?;
+
+            // The sire stays with its owner, who is paid for the service
+            if let Err(e) = <balances::Module<T>>::make_transfer(&sender, &sire_owner, fee) {
+                <balances::Module<T>>::unreserve(&matron_owner, deposit);
+                return Err(e);
+            }
+
+            Self::_conceive(matron_owner, matron_id, sire_id, None, deposit)?;
+
+            Self::deposit_event(RawEvent::SireUsed(sender, sire_owner, matron_id, sire_id, fee));
+
+            Ok(())
+        }
+
+        fn set_max_kitties_per_account(origin, max: Option<u64>) -> Result {
+            ensure_root(origin)?;
+
+            match max {
+                Some(max) => <MaxKittiesPerAccountOverride<T>>::put(max),
+                None => <MaxKittiesPerAccountOverride<T>>::kill(),
+            }
+
+            Ok(())
+        }
+
+        fn set_max_gen0_supply(origin, max: Option<u64>) -> Result {
+            ensure_root(origin)?;
+
+            match max {
+                Some(max) => <MaxGen0SupplyOverride<T>>::put(max),
+                None => <MaxGen0SupplyOverride<T>>::kill(),
+            }
+
+            Ok(())
+        }
+
+        fn set_public_creation(origin, enabled: bool) -> Result {
+            ensure_root(origin)?;
+
+            <PublicCreationEnabled<T>>::put(enabled);
+
+            Ok(())
+        }
+
+        fn set_instant_randomness(origin, enabled: bool) -> Result {
+            ensure_root(origin)?;
+
+            <InstantRandomnessEnabled<T>>::put(enabled);
+
+            Ok(())
+        }
+
+        fn mint_promo(origin, to: T::AccountId, dna: T::Hash) -> Result {
+            ensure_root(origin)?;
+
+            let promo_count = Self::promo_count();
+            ensure!(promo_count < T::PROMO_QUOTA, "The promo quota is exhausted.");
+
+            // Promo kitties keep their DNA as given, mutations included; see the `genetics` docs
+            let new_kitty = Kitty {
+                id: dna,
+                dna: dna,
+                price: <T::Balance as As<u64>>::sa(0),
+                gen: 0,
+                cooldown_end: <T::BlockNumber as As<u64>>::sa(0),
+                breed_count: 0
+            };
+
+            Self::_mint(to, dna, new_kitty, Zero::zero())?;
+
+            <PromoCount<T>>::put(promo_count + 1);
+
+            Ok(())
+        }
+
+        fn set_royalty(origin, royalty: Permill) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(royalty * 1_000_000u64 <= T::MAX_ROYALTY_PARTS as u64, "The royalty is higher than allowed.");
+
+            <CreatorRoyalty<T>>::insert(&sender, royalty);
+
+            Ok(())
+        }
+
+        fn set_market_fee(origin, fee: Permill) -> Result {
+            ensure_root(origin)?;
+
+            // The seller must be left with something after the fee and the royalty
+            ensure!(
+                fee * 1_000_000u64 + T::MAX_ROYALTY_PARTS as u64 <= 1_000_000,
+                "The market fee leaves no room for royalties."
+            );
+
+            <MarketFee<T>>::put(fee);
+
+            Ok(())
+        }
+
+        fn set_creation_deposit(origin, deposit: T::Balance) -> Result {
+            ensure_root(origin)?;
+
+            <CreationDeposit<T>>::put(deposit);
+
+            Ok(())
+        }
+
+        fn burn_kitty(origin, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty.")?;
+            ensure!(owner == sender, "You do not own this kitty.");
+
+            Self::_burn(sender, kitty_id)?;
+
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+  /// How many kitties a single account may own right now.
+  pub fn max_kitties_per_account() -> u64 {
+    Self::max_kitties_per_account_override().unwrap_or(T::MAX_KITTIES_PER_ACCOUNT)
+  }
+
+  /// How many gen 0 kitties may be minted in total right now.
+  pub fn max_gen0_supply() -> u64 {
+    Self::max_gen0_supply_override().unwrap_or(T::MAX_GEN0_SUPPLY)
+  }
+
+  /// The traits `kitty_id` shows, if it exists.
+  pub fn phenotype_of(kitty_id: T::Hash) -> Option<Phenotype> {
+    if !<Kitties<T>>::exists(kitty_id) {
+      return None;
+    }
+
+    Some(Phenotype::from_dna(Self::kitty(kitty_id).dna.as_ref()))
+  }
+
+  /// Returns the owner of `kitty_id` if `who` is that owner or one of their approved operators.
+  fn _ensure_owner_or_operator(who: &T::AccountId, kitty_id: T::Hash) -> rstd::result::Result<T::AccountId, &'static str> {
+    let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty.")?;
+    ensure!(Self::_is_owner_or_operator(who, &owner), "You do not own or operate this kitty.");
+
+    Ok(owner)
+  }
+
+  fn _is_owner_or_operator(who: &T::AccountId, owner: &T::AccountId) -> bool {
+    who == owner || Self::is_approved_for_all((owner.clone(), who.clone()))
+  }
+
+  /// Kitties that are locked can not change hands, be put up for sale or be burned.
+  fn _ensure_unlocked(kitty_id: T::Hash) -> Result {
+    ensure!(!<Pregnancies<T>>::exists(kitty_id), "This kitty is pregnant.");
+    ensure!(!<ReservedForBreeding<T>>::exists(kitty_id), "This kitty is reserved for breeding.");
+    ensure!(!Self::_is_on_auction(kitty_id), "This kitty is on auction.");
+    ensure!(!<LockedInSwap<T>>::exists(kitty_id), "This kitty is locked in a swap.");
+
+    Ok(())
+  }
+
+  /// Checks that `buyer` can pay `price` for `kitty_id` and take it over, before any money moves.
+  fn _ensure_can_buy(buyer: &T::AccountId, kitty_id: T::Hash, price: T::Balance) -> Result {
+    // The buyer takes over the creation deposit too
+    let total_cost = price.checked_add(&Self::deposit_of(kitty_id))
+      .ok_or("Overflow when adding the creation deposit to the price.")?;
+    ensure!(
+      <balances::Module<T>>::free_balance(buyer) >= total_cost,
+      "You cannot afford the kitty and its creation deposit."
+    );
+    ensure!(
+      Self::owned_kitty_count(buyer) < Self::max_kitties_per_account(),
+      "The receiver owns too many kitties."
+    );
+
+    Ok(())
+  }
+
+  /// Checks that `who` can take over `kitty_id`, paid for with funds that are already reserved.
+  fn _ensure_can_receive(who: &T::AccountId, kitty_id: T::Hash) -> Result {
+    Self::_ensure_unlocked(kitty_id)?;
+    ensure!(
+      Self::owned_kitty_count(who) < Self::max_kitties_per_account(),
+      "The receiver owns too many kitties."
+    );
+    ensure!(
+      <balances::Module<T>>::free_balance(who) >= Self::deposit_of(kitty_id),
+      "The buyer cannot afford the creation deposit."
+    );
+
+    Ok(())
+  }
+
+  fn _is_on_auction(kitty_id: T::Hash) -> bool {
+    <DutchAuctions<T>>::exists(kitty_id) || <Auctions<T>>::exists(kitty_id)
+  }
+
+  /// Ends the auction of `kitty_id`, selling it to the top bidder if the reserve was met.
+  fn _settle_auction(kitty_id: T::Hash) {
+    let auction = match <Auctions<T>>::take(kitty_id) {
+      Some(auction) => auction,
+      None => return,
+    };
+
+    let (winner, price) = match auction.top_bid {
+      Some(top_bid) => top_bid,
+      None => {
+        Self::deposit_event(RawEvent::AuctionSettled(kitty_id, None, Zero::zero()));
+        return;
+      }
+    };
+
+    let seller = match Self::owner_of(kitty_id) {
+      Some(seller) => seller,
+      None => {
+        <balances::Module<T>>::unreserve(&winner, price);
+        return;
+      }
+    };
+
+    // Check everything up front, so that the winner keeps their money if the sale cannot go through
+    let split = if price >= auction.reserve && Self::_ensure_can_receive(&winner, kitty_id).is_ok() {
+      Self::_split_price(&seller, kitty_id, price).ok()
+    } else {
+      None
+    };
+    let split = match split {
+      Some(split) => split,
+      None => {
+        <balances::Module<T>>::unreserve(&winner, price);
+        Self::deposit_event(RawEvent::AuctionSettled(kitty_id, None, Zero::zero()));
+        return;
+      }
+    };
+
+    Self::_pay_reserved(&winner, &seller, kitty_id, split);
+    // Cannot fail, as `_ensure_can_receive` checked the winner can take the kitty
+    let _ = Self::_transfer_from(seller.clone(), winner.clone(), kitty_id);
+
+    Self::deposit_event(RawEvent::AuctionSettled(kitty_id, Some(winner), price));
+  }
+
+  /// Forgets `swap_id` without executing it, unlocking the proposer's kitties and money.
+  fn _release_swap(swap_id: T::Hash) -> Option<Swap<T::AccountId, T::Hash, T::Balance, T::BlockNumber>> {
+    let swap = <Swaps<T>>::take(swap_id)?;
+
+    for &kitty_id in swap.offered.iter() {
+      <LockedInSwap<T>>::remove(kitty_id);
+    }
+    if let BalanceDelta::ProposerPays(amount) = swap.balance_delta {
+      <balances::Module<T>>::unreserve(&swap.proposer, amount);
+    }
+
+    Some(swap)
+  }
+
+  /// Takes `kitty_id` off the fixed price market once its listing ran out.
+  fn _expire_listing(kitty_id: T::Hash) {
+    <ListingExpiry<T>>::remove(kitty_id);
+
+    let mut kitty = Self::kitty(kitty_id);
+    kitty.price = Zero::zero();
+    <Kitties<T>>::insert(kitty_id, kitty);
+
+    if let Some(owner) = Self::owner_of(kitty_id) {
+      Self::deposit_event(RawEvent::ListingExpired(owner, kitty_id));
+    }
+  }
+
+  /// Forgets the offer of `buyer` on `kitty_id`, leaving its funds reserved.
+  fn _remove_offer(kitty_id: T::Hash, buyer: &T::AccountId) -> Option<Offer<T::Balance, T::BlockNumber>> {
+    let offer = <Offers<T>>::take((kitty_id, buyer.clone()))?;
+
+    let mut buyers = Self::offers_for(kitty_id);
+    buyers.retain(|b| b != buyer);
+    if buyers.is_empty() {
+      <OffersFor<T>>::remove(kitty_id);
+    } else {
+      <OffersFor<T>>::insert(kitty_id, buyers);
+    }
+
+    Some(offer)
+  }
+
+  /// Forgets the offer of `buyer` on `kitty_id`, if any, and returns its funds.
+  fn _withdraw_offer(kitty_id: T::Hash, buyer: T::AccountId) {
+    if let Some(offer) = Self::_remove_offer(kitty_id, &buyer) {
+      <balances::Module<T>>::unreserve(&buyer, offer.amount);
+      Self::deposit_event(RawEvent::OfferWithdrawn(buyer, kitty_id));
+    }
+  }
+
+  /// Sells `kitty_id` from `seller` to `buyer` for `price`, shared out as in `split`. The caller
+  /// checks with `_ensure_can_buy` that the buyer can pay and take the kitty, so that either both
+  /// the money and the kitty move, or neither.
+  fn _sell(
+    seller: T::AccountId,
+    buyer: T::AccountId,
+    kitty_id: T::Hash,
+    price: T::Balance,
+    split: PriceSplit<T::AccountId, T::Balance>
+  ) -> Result {
+    <balances::Module<T>>::reserve(&buyer, price).map_err(|_| "You cannot afford this kitty.")?;
+
+    Self::_pay_reserved(&buyer, &seller, kitty_id, split);
+    Self::_transfer_from(seller, buyer, kitty_id)
+  }
+
+  /// Shares `price` for `kitty_id` out into the market fee, the royalty owed to its creator, if
+  /// any, and what is left for `seller`. Fails if the money cannot be paid out that way.
+  fn _split_price(
+    seller: &T::AccountId,
+    kitty_id: T::Hash,
+    price: T::Balance
+  ) -> rstd::result::Result<PriceSplit<T::AccountId, T::Balance>, &'static str> {
+    // `repatriate_reserved` only pays into accounts that exist
+    let exists = |who: &T::AccountId| !<balances::Module<T>>::total_balance(who).is_zero();
+
+    let fee = Self::market_fee() * price;
+    ensure!(fee.is_zero() || exists(&Self::fee_collector()), "The fee collector account does not exist.");
+    let mut proceeds = price.checked_sub(&fee).ok_or("The market fee is more than the price.")?;
+
+    // A creator whose account is gone forgoes the royalty rather than blocking the sale
+    let royalty = match Self::creator_of(kitty_id) {
+      Some(creator) if creator != *seller && exists(&creator) => {
+        let royalty = Self::royalty_of(&creator) * price;
+        if royalty.is_zero() { None } else { Some((creator, royalty)) }
+      },
+      _ => None,
+    };
+    if let Some((_, royalty)) = &royalty {
+      proceeds = proceeds.checked_sub(royalty).ok_or("The market fee and royalty are more than the price.")?;
+    }
+
+    ensure!(exists(seller), "The seller's account does not exist.");
+
+    Ok(PriceSplit {
+      fee: fee,
+      royalty: royalty,
+      proceeds: proceeds,
+    })
+  }
+
+  /// Pays out a price reserved from `buyer` for `kitty_id`, as shared out by `_split_price`.
+  fn _pay_reserved(buyer: &T::AccountId, seller: &T::AccountId, kitty_id: T::Hash, split: PriceSplit<T::AccountId, T::Balance>) {
+    // `_split_price` made sure every payee exists, so none of these can fail
+    if !split.fee.is_zero() {
+      let _ = <balances::Module<T>>::repatriate_reserved(buyer, &Self::fee_collector(), split.fee);
+      Self::deposit_event(RawEvent::MarketFeeCollected(kitty_id, split.fee));
+    }
+
+    if let Some((creator, royalty)) = split.royalty {
+      let _ = <balances::Module<T>>::repatriate_reserved(buyer, &creator, royalty);
+      Self::deposit_event(RawEvent::RoyaltyPaid(creator, kitty_id, royalty));
+    }
+
+    let _ = <balances::Module<T>>::repatriate_reserved(buyer, seller, split.proceeds);
+  }
+
+  /// The price of a dutch auction at block `now`.
+  fn _dutch_price(auction: &DutchAuction<T::Balance, T::BlockNumber>, now: T::BlockNumber) -> T::Balance {
+    let elapsed = (now - auction.started_at).as_();
+    let duration = auction.duration.as_();
+    if elapsed >= duration {
+      return auction.end_price;
+    }
+
+    let range = auction.start_price - auction.end_price;
+    let elapsed = <T::Balance as As<u64>>::sa(elapsed);
+    let duration = <T::Balance as As<u64>>::sa(duration);
+    // Dividing first keeps large prices from overflowing, and the remainder keeps the precision
+    let drop = range / duration * elapsed + range % duration * elapsed / duration;
+
+    auction.start_price - drop
+  }
+
+  fn _commit(who: T::AccountId, kind: CommitmentKind<T::Hash>, hash: T::Hash) -> Result {
+    ensure!(!<Commitments<T>>::exists(&who), "You already have an outstanding commitment.");
+
+    let bond = <T::Balance as As<u64>>::sa(T::COMMITMENT_BOND);
+    <balances::Module<T>>::reserve(&who, bond).map_err(|_| "You cannot afford the commitment bond.")?;
+
+    let now = <system::Module<T>>::block_number();
+    let expires_at = now + <T::BlockNumber as As<u64>>::sa(T::REVEAL_WINDOW);
+
+    let commitment = Commitment {
+      kind: kind,
+      hash: hash,
+      committed_at: now,
+      expires_at: expires_at,
+      bond: bond,
+    };
+
+    <Commitments<T>>::insert(&who, commitment);
+    <RevealSeedNeeded<T>>::insert(now + <T::BlockNumber as As<u64>>::sa(1), true);
+    <CommitmentsExpiring<T>>::mutate(expires_at, |expiring| expiring.push(who.clone()));
+
+    Self::deposit_event(RawEvent::Committed(who, hash, expires_at));
+
+    Ok(())
+  }
+
+  /// Checks `secret` against the commitment of `who`, returning that commitment and the
+  /// randomness it yields.
+  fn _reveal(
+    who: &T::AccountId,
+    secret: T::Hash
+  ) -> rstd::result::Result<(Commitment<T::Hash, T::BlockNumber, T::Balance>, T::Hash), &'static str> {
+    let commitment = Self::commitment_of(who).ok_or("You have no outstanding commitment.")?;
+
+    let now = <system::Module<T>>::block_number();
+    ensure!(now <= commitment.expires_at, "The commitment has expired.");
+    ensure!(
+      <T as system::Trait>::Hashing::hash_of(&secret) == commitment.hash,
+      "The secret does not match your commitment."
+    );
+
+    // The same seed is used whichever block the secret is revealed in, so waiting for a
+    // better one does not help
+    let block_seed = Self::reveal_seed_of(commitment.committed_at + <T::BlockNumber as As<u64>>::sa(1))
+      .ok_or("The secret can only be revealed after the block following the commitment.")?;
+    let random_hash = (secret, block_seed).using_encoded(<T as system::Trait>::Hashing::hash);
+
+    Ok((commitment, random_hash))
+  }
+
+  fn _expire_commitment(who: T::AccountId) {
+    if let Some(commitment) = <Commitments<T>>::take(&who) {
+      if let CommitmentKind::Breed(matron_id, sire_id) = commitment.kind {
+        <ReservedForBreeding<T>>::remove(matron_id);
+        <ReservedForBreeding<T>>::remove(sire_id);
+      }
+      let _ = <balances::Module<T>>::slash_reserved(&who, commitment.bond);
+
+      Self::deposit_event(RawEvent::CommitmentExpired(who, commitment.hash));
+    }
+  }
+
+  /// Checks that `sender` may breed `matron_id` with `sire_id` right now, returning the
+  /// matron's owner and whether `sender` controls the sire rather than relying on a sire allowance.
+  fn _ensure_may_breed(sender: &T::AccountId, matron_id: T::Hash, sire_id: T::Hash) -> rstd::result::Result<(T::AccountId, bool), &'static str> {
+    ensure!(<Kitties<T>>::exists(matron_id), "The matron does not exist.");
+    ensure!(<Kitties<T>>::exists(sire_id), "The sire does not exist.");
+    ensure!(matron_id != sire_id, "A kitty cannot breed with itself.");
+
+    let matron_owner = Self::owner_of(matron_id).ok_or("No owner for the matron.")?;
+    ensure!(Self::_is_owner_or_operator(sender, &matron_owner), "You do not own the matron.");
+    Self::_ensure_can_take_child(&matron_owner)?;
+
+    // The sire must be ours too, unless its owner allowed us to use it
+    let sire_owner = Self::owner_of(sire_id).ok_or("No owner for the sire.")?;
+    let controls_sire = Self::_is_owner_or_operator(sender, &sire_owner);
+    ensure!(
+      controls_sire || Self::sire_allowed_to(sire_id).as_ref() == Some(sender),
+      "You are not allowed to breed with the sire."
+    );
+
+    Self::_ensure_not_related(matron_id, sire_id)?;
+    Self::_ensure_ready_to_breed(matron_id, sire_id)?;
+
+    Ok((matron_owner, controls_sire))
+  }
+
+  fn _ensure_can_take_child(owner: &T::AccountId) -> Result {
+    ensure!(
+      Self::owned_kitty_count(owner) < Self::max_kitties_per_account(),
+      "The matron's owner cannot take another kitty."
+    );
+
+    Ok(())
+  }
+
+  fn _ensure_not_related(matron_id: T::Hash, sire_id: T::Hash) -> Result {
+    let matron_parents = Self::parents_of(matron_id);
+    let sire_parents = Self::parents_of(sire_id);
+
+    if let Some((matron_matron, matron_sire)) = matron_parents {
+      ensure!(matron_matron != sire_id && matron_sire != sire_id, "A kitty cannot breed with its parent.");
+    }
+    if let Some((sire_matron, sire_sire)) = sire_parents {
+      ensure!(sire_matron != matron_id && sire_sire != matron_id, "A kitty cannot breed with its child.");
+    }
+    if let (Some(matron_parents), Some(sire_parents)) = (matron_parents, sire_parents) {
+      let full_siblings = matron_parents == sire_parents
+        || (matron_parents.0 == sire_parents.1 && matron_parents.1 == sire_parents.0);
+      ensure!(!full_siblings, "Full siblings cannot breed.");
+    }
+
+    Ok(())
+  }
+
+  fn _ensure_ready_to_breed(matron_id: T::Hash, sire_id: T::Hash) -> Result {
+    let now = <system::Module<T>>::block_number();
+
+    ensure!(!<ReservedForBreeding<T>>::exists(matron_id), "The matron is reserved for breeding.");
+    ensure!(!Self::_is_on_auction(matron_id), "The matron is on auction.");
+    ensure!(!<LockedInSwap<T>>::exists(matron_id), "The matron is locked in a swap.");
+    ensure!(!<Pregnancies<T>>::exists(matron_id), "The matron is pregnant.");
+    ensure!(Self::kitty(matron_id).cooldown_end <= now, "The matron is still cooling down.");
+    ensure!(!<ReservedForBreeding<T>>::exists(sire_id), "The sire is reserved for breeding.");
+    ensure!(!Self::_is_on_auction(sire_id), "The sire is on auction.");
+    ensure!(!<LockedInSwap<T>>::exists(sire_id), "The sire is locked in a swap.");
+    ensure!(!<Pregnancies<T>>::exists(sire_id), "The sire is pregnant.");
+    ensure!(Self::kitty(sire_id).cooldown_end <= now, "The sire is still cooling down.");
+
+    Ok(())
+  }
+
+  /// Makes `matron_id` pregnant by `sire_id`; the child will belong to `breeder`, the matron's owner.
+  fn _conceive(breeder: T::AccountId, matron_id: T::Hash, sire_id: T::Hash, seed: Option<T::Hash>, deposit: T::Balance) -> Result {
+    let matron = Self::kitty(matron_id);
+    let sire = Self::kitty(sire_id);
+
+    let now = <system::Module<T>>::block_number();
+    let due_at = now + T::BreedingSchedule::gestation(matron.gen);
+
+    let pregnancy = Pregnancy {
+      sire: sire_id,
+      sire_dna: sire.dna,
+      sire_gen: sire.gen,
+      breeder: breeder.clone(),
+      conceived_at: now,
+      due_at: due_at,
+      seed: seed,
+      deposit: deposit,
+    };
+
+    <Pregnancies<T>>::insert(matron_id, pregnancy);
+    <PregnanciesDue<T>>::mutate(due_at, |due| due.push(matron_id));
+
+    // Both parents need to rest before they can breed again
+    Self::_start_cooldown(matron_id);
+    Self::_start_cooldown(sire_id);
+
+    Self::deposit_event(RawEvent::Conceived(breeder, matron_id, sire_id, due_at));
+
+    Ok(())
+  }
+
+  /// Ends the pregnancy of `matron_id`, minting the child with randomness from the current block.
+  fn _give_birth(matron_id: T::Hash) -> Result {
+    let pregnancy = Self::pregnancy_of(matron_id).ok_or("This kitty is not pregnant.")?;
+
+    let random_hash = T::Randomness::random(&matron_id.encode());
+
+    let matron = Self::kitty(matron_id);
+
+    let seed = pregnancy.seed.unwrap_or(random_hash);
+    let final_dna = T::GeneSplicer::splice(&matron.dna, &pregnancy.sire_dna, &seed);
+
+    let new_kitty = Kitty {
+      id: random_hash,
+      dna: final_dna,
+      price: <T::Balance as As<u64>>::sa(0),
+      gen: rstd::cmp::max(matron.gen, pregnancy.sire_gen) + 1,
+      cooldown_end: <T::BlockNumber as As<u64>>::sa(0),
+      breed_count: 0
+    };
+
+    Self::_mint(pregnancy.breeder, random_hash, new_kitty, pregnancy.deposit)?;
+
+    <Pregnancies<T>>::remove(matron_id);
+
+    <KittyParents<T>>::insert(random_hash, (matron_id, pregnancy.sire));
+    Self::_add_child(matron_id, random_hash);
+    Self::_add_child(pregnancy.sire, random_hash);
+
+    Self::deposit_event(RawEvent::Born(matron_id, pregnancy.sire, random_hash));
+
+    Ok(())
+  }
+
+  /// Ends the pregnancy of `matron_id` without a child, returning the creation deposit.
+  fn _lose_pregnancy(matron_id: T::Hash) {
+    if let Some(pregnancy) = <Pregnancies<T>>::take(matron_id) {
+      <balances::Module<T>>::unreserve(&pregnancy.breeder, pregnancy.deposit);
+
+      Self::deposit_event(RawEvent::PregnancyLost(matron_id, pregnancy.breeder));
+    }
+  }
+
+  fn _start_cooldown(kitty_id: T::Hash) {
+    let now = <system::Module<T>>::block_number();
+
+    <Kitties<T>>::mutate(kitty_id, |kitty| {
+      kitty.cooldown_end = now + T::BreedingSchedule::cooldown(kitty.gen, kitty.breed_count);
+      kitty.breed_count = kitty.breed_count.saturating_add(1);
+    });
+  }
+
+  fn _reserve_creation_deposit(who: &T::AccountId) -> rstd::result::Result<T::Balance, &'static str> {
+    let deposit = Self::creation_deposit();
+    <balances::Module<T>>::reserve(who, deposit).map_err(|_| "You cannot afford the creation deposit.")?;
+
+    Ok(deposit)
+  }
+
+  /// Mints a kitty for `owner`, who pays the creation deposit for it.
+  fn _create(owner: T::AccountId, kitty_id: T::Hash, new_kitty: Kitty<T::Hash, T::Balance, T::BlockNumber>) -> Result {
+    let deposit = Self::_reserve_creation_deposit(&owner)?;
+
+    Self::_mint(owner.clone(), kitty_id, new_kitty, deposit).map_err(|e| {
+      <balances::Module<T>>::unreserve(&owner, deposit);
+      e
+    })
+  }
+
+  fn _add_child(parent: T::Hash, child: T::Hash) {
+    let child_count = Self::child_count(parent);
+    <KittyChildrenArray<T>>::insert((parent, child_count), child);
+    <KittyChildrenCount<T>>::insert(parent, child_count + 1);
+  }
+
+  /// Mints a kitty for `to`, who already reserved `deposit` for it.
+  fn _mint(to: T::AccountId, kitty_id: T::Hash, new_kitty: Kitty<T::Hash, T::Balance, T::BlockNumber>, deposit: T::Balance) -> Result {
+    ensure!(!<Kitties<T>>::exists(kitty_id), "The kitty already exists");
+
+    let owned_kitty_count = Self::owned_kitty_count(&to);
+    let new_owned_kitty_count = owned_kitty_count
+      .checked_add(1)
+      .ok_or("Over 18 quintillion for the user!!! Too many kitties!")?;
+
+    ensure!(new_owned_kitty_count <= Self::max_kitties_per_account(), "This account owns too many kitties.");
+
+    let all_kitties_count = Self::all_kitties_count();
+    let new_all_kitties_count = all_kitties_count
+      .checked_add(1)
+      .ok_or("Over 18 quintillion!!! Too many kitties!")?;
+
+    let is_gen0 = new_kitty.gen == 0;
+    if is_gen0 {
+      ensure!(Self::gen0_count() < Self::max_gen0_supply(), "The gen 0 supply is exhausted.");
+    }
+
+    // Update new kitty store
+    <Kitties<T>>::insert(kitty_id, new_kitty);
+    <KittyOwner<T>>::insert(kitty_id, &to);
+
+    // Update global kitties tracking
+    <AllKittiesArray<T>>::insert(all_kitties_count, kitty_id);
+    <AllKittiesIndex<T>>::insert(kitty_id, all_kitties_count);
+    <AllKittiesCount<T>>::put(new_all_kitties_count);
+
+    <OwnedKittiesArray<T>>::insert((to.clone(), owned_kitty_count), kitty_id);
+    <OwnedKittiesIndex<T>>::insert(kitty_id, owned_kitty_count);
+    <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count);
+
+    if is_gen0 {
+      <Gen0Count<T>>::mutate(|count| *count += 1);
+    }
+    <KittyCreator<T>>::insert(kitty_id, &to);
+    if !deposit.is_zero() {
+      <KittyDeposit<T>>::insert(kitty_id, deposit);
+    }
+
+    Self::deposit_event(RawEvent::Created(to, kitty_id));
+
+    Ok(())
+  }
+
+  fn _transfer_from(from: T::AccountId, to: T::AccountId, kitty_id: T::Hash) -> Result {
+    ensure!(
+      Self::owned_kitty_count(&to) < Self::max_kitties_per_account(),
+      "The receiver owns too many kitties."
+    );
+    Self::_move_kitty(from.clone(), to.clone(), kitty_id)?;
+
+    // Deposit a `Transferred` event with the relevant data:
+    //      - from
+    //      - to
+    //      - kitty_id
+    Self::deposit_event(RawEvent::Transferred(from, to, kitty_id));
+
+    Ok(())
+  }
+
+  /// Moves `kitty_id` from `from` to `to` without depositing an event. The receiver's kitty cap
+  /// is left to the caller, since moving several kitties at once only needs the final count to fit.
+  fn _move_kitty(from: T::AccountId, to: T::AccountId, kitty_id: T::Hash) -> Result {
+    // Check if owner exists for `kitty_id`
+    //      - If it does, sanity check that `from` is the `owner`
+    //      - If it doesn't, return an `Err()` that no `owner` exists
+    let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty.")?;
+    ensure!(owner == from, "The 'from' account does not own this kitty.");
+    // Moving a kitty to its owner would corrupt the owner's enumeration
+    ensure!(from != to, "You cannot transfer a kitty to its owner.");
+    Self::_ensure_unlocked(kitty_id)?;
+
+    let owned_kitty_count_from = Self::owned_kitty_count(&from);
+    let owned_kitty_count_to = Self::owned_kitty_count(&to);
+
+    // Used `checked_add()` to increment the `owned_kitty_count_to` by one into `new_owned_kitty_count_to`
     // Used `checked_sub()` to increment the `owned_kitty_count_from` by one into `new_owned_kitty_count_from`
     //      - Return an `Err()` if overflow or underflow
     let new_owned_kitty_count_to = owned_kitty_count_to
@@ -267,6 +2105,14 @@ impl<T: Trait> Module<T> {
       .checked_sub(1)
       .ok_or("No kitty available to transfer from this account.")?;
 
+    // The creation deposit follows the kitty to its new owner
+    let deposit = Self::deposit_of(kitty_id);
+    if !deposit.is_zero() {
+      <balances::Module<T>>::reserve(&to, deposit)
+        .map_err(|_| "The receiver cannot afford the creation deposit.")?;
+      <balances::Module<T>>::unreserve(&from, deposit);
+    }
+
     // "Swap and pop"
     // We our convenience storage items to help simplify removing an element from the OwnedKittiesArray
     // We switch the last element of OwnedKittiesArray with the element we want to remove
@@ -278,8 +2124,20 @@ impl<T: Trait> Module<T> {
     }
     // Now we can remove this item by removing the last element
 
-    // Update KittyOwner for `kitty_id`
+    // Update KittyOwner for `kitty_id`, dropping any approval the old owner gave
     <KittyOwner<T>>::insert(kitty_id, &to);
+    <Approvals<T>>::remove(kitty_id);
+    <SireAllowedTo<T>>::remove(kitty_id);
+    <SirePrice<T>>::remove(kitty_id);
+    // The old owner's listing must not let anyone buy the kitty from the new one
+    let mut kitty = Self::kitty(kitty_id);
+    if !kitty.price.is_zero() {
+      kitty.price = Zero::zero();
+      <Kitties<T>>::insert(kitty_id, kitty);
+    }
+    <ListingExpiry<T>>::remove(kitty_id);
+    // The new owner cannot buy the kitty from themselves; other offers stay up for them
+    Self::_withdraw_offer(kitty_id, to.clone());
     // Update OwnedKittiesIndex for `kitty_id`
     <OwnedKittiesIndex<T>>::insert(kitty_id, owned_kitty_count_to);
 
@@ -293,12 +2151,1458 @@ impl<T: Trait> Module<T> {
     <OwnedKittiesCount<T>>::insert(&from, new_owned_kitty_count_from);
     <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count_to);
 
-    // Deposit a `Transferred` event with the relevant data:
-    //      - from
-    //      - to
-    //      - kitty_id
-    Self::deposit_event(RawEvent::Transferred(from, to, kitty_id));
+    Ok(())
+  }
+
+  fn _burn(owner: T::AccountId, kitty_id: T::Hash) -> Result {
+    ensure!(Self::owner_of(kitty_id) == Some(owner.clone()), "The 'owner' account does not own this kitty.");
+    Self::_ensure_unlocked(kitty_id)?;
+
+    let new_owned_kitty_count = Self::owned_kitty_count(&owner)
+      .checked_sub(1)
+      .ok_or("No kitty available to burn from this account.")?;
+
+    let new_all_kitties_count = Self::all_kitties_count()
+      .checked_sub(1)
+      .ok_or("No kitty available to burn.")?;
+
+    // "Swap and pop" the kitty out of the owner's enumeration
+    let owned_index = <OwnedKittiesIndex<T>>::get(kitty_id);
+    if owned_index != new_owned_kitty_count {
+      let last_kitty_id = <OwnedKittiesArray<T>>::get((owner.clone(), new_owned_kitty_count));
+      <OwnedKittiesArray<T>>::insert((owner.clone(), owned_index), last_kitty_id);
+      <OwnedKittiesIndex<T>>::insert(last_kitty_id, owned_index);
+    }
+    <OwnedKittiesArray<T>>::remove((owner.clone(), new_owned_kitty_count));
+    <OwnedKittiesIndex<T>>::remove(kitty_id);
+    <OwnedKittiesCount<T>>::insert(&owner, new_owned_kitty_count);
+
+    // ...and out of the global enumeration
+    let all_index = <AllKittiesIndex<T>>::get(kitty_id);
+    if all_index != new_all_kitties_count {
+      let last_kitty_id = <AllKittiesArray<T>>::get(new_all_kitties_count);
+      <AllKittiesArray<T>>::insert(all_index, last_kitty_id);
+      <AllKittiesIndex<T>>::insert(last_kitty_id, all_index);
+    }
+    <AllKittiesArray<T>>::remove(new_all_kitties_count);
+    <AllKittiesIndex<T>>::remove(kitty_id);
+    <AllKittiesCount<T>>::put(new_all_kitties_count);
+
+    <Kitties<T>>::remove(kitty_id);
+    <KittyOwner<T>>::remove(kitty_id);
+    <KittyCreator<T>>::remove(kitty_id);
+    <ListingExpiry<T>>::remove(kitty_id);
+    <Approvals<T>>::remove(kitty_id);
+    <SireAllowedTo<T>>::remove(kitty_id);
+    <SirePrice<T>>::remove(kitty_id);
+    for buyer in Self::offers_for(kitty_id) {
+      Self::_withdraw_offer(kitty_id, buyer);
+    }
+
+    let deposit = <KittyDeposit<T>>::take(kitty_id);
+    <balances::Module<T>>::unreserve(&owner, deposit);
+
+    Self::deposit_event(RawEvent::Burned(owner, kitty_id));
 
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use primitives::{Blake2Hasher, H256};
+  use runtime_io::with_externalities;
+  use runtime_primitives::{
+    testing::{Digest, DigestItem, Header},
+    traits::{BlakeTwo256, IdentityLookup, OnFinalise},
+    BuildStorage,
+  };
+  use std::cell::Cell;
+  use support::{assert_noop, assert_ok, impl_outer_origin};
+
+  impl_outer_origin! {
+    pub enum Origin for Test {}
+  }
+
+  #[derive(Clone, Eq, PartialEq)]
+  pub struct Test;
+  impl system::Trait for Test {
+    type Origin = Origin;
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type Digest = Digest;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<u64>;
+    type Header = Header;
+    type Event = ();
+    type Log = DigestItem;
+  }
+  impl balances::Trait for Test {
+    type Balance = u64;
+    type OnFreeBalanceZero = ();
+    type OnNewAccount = ();
+    type EnsureAccountLiquid = ();
+    type Event = ();
+  }
+  thread_local! {
+    static MOCK_NONCE: Cell<u64> = Cell::new(0);
+  }
+
+  /// Ignores the block and simply counts, so every test sees the same ids and DNA.
+  pub struct MockRandomness;
+  impl Randomness<H256> for MockRandomness {
+    fn random(subject: &[u8]) -> H256 {
+      let nonce = MOCK_NONCE.with(|n| {
+        let nonce = n.get();
+        n.set(nonce + 1);
+        nonce
+      });
+      (subject, nonce).using_encoded(BlakeTwo256::hash)
+    }
+  }
+
+  impl Trait for Test {
+    type Event = ();
+    type BreedingSchedule = DefaultBreedingSchedule;
+    const MUTATION_CHANCE: u8 = 32;
+    type GeneSplicer = MendelianSplicer<Test>;
+    type Randomness = MockRandomness;
+    const REVEAL_WINDOW: u64 = 5;
+    const COMMITMENT_BOND: u64 = 100;
+    const MAX_KITTIES_PER_ACCOUNT: u64 = 100;
+    const MAX_GEN0_SUPPLY: u64 = 100;
+    const PROMO_QUOTA: u64 = 2;
+    const MAX_ROYALTY_PARTS: u32 = 100_000;
+    const MAX_OFFER_DURATION: u64 = 100;
+    const MAX_AUCTION_DURATION: u64 = 100;
+  }
+  type Kitties = Module<Test>;
+
+  fn dna(byte: u8) -> H256 {
+    H256::from([byte; 32])
+  }
+
+  const FEE_COLLECTOR: u64 = 9;
+
+  fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+    MOCK_NONCE.with(|n| n.set(0));
+
+    let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
+    t.extend(
+      balances::GenesisConfig::<Test> {
+        existential_deposit: 0,
+        transfer_fee: 0,
+        creation_fee: 0,
+        balances: vec![(1, 10_000), (2, 10_000), (3, 10_000), (FEE_COLLECTOR, 1)],
+        vesting: vec![],
+      }
+      .build_storage()
+      .unwrap()
+      .0,
+    );
+    t.extend(
+      GenesisConfig::<Test> {
+        kitties: vec![(1, dna(1), 0, 0), (1, dna(2), 100, 0), (2, dna(3), 0, 1)],
+        creation_deposit: 0,
+        public_creation_enabled: true,
+        instant_randomness_enabled: true,
+        market_fee: Permill::zero(),
+        fee_collector: FEE_COLLECTOR,
+      }
+      .build_storage()
+      .unwrap()
+      .0,
+    );
+    t.into()
+  }
+
+  #[test]
+  fn genesis_kitties_are_indexed() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_eq!(Kitties::all_kitties_count(), 3);
+      assert_eq!(Kitties::all_kitties_by_index(1), dna(2));
+      assert_eq!(<AllKittiesIndex<Test>>::get(dna(3)), 2);
+
+      assert_eq!(Kitties::owned_kitty_count(1), 2);
+      assert_eq!(Kitties::owned_kitty_count(2), 1);
+      assert_eq!(Kitties::owned_kitty_by_index((1, 1)), dna(2));
+      assert_eq!(Kitties::owned_kitty_by_index((2, 0)), dna(3));
+
+      assert_eq!(Kitties::owner_of(dna(2)), Some(1));
+      assert_eq!(Kitties::kitty(dna(2)).price, 100);
+      assert_eq!(Kitties::kitty(dna(3)).gen, 1);
+    });
+  }
+
+  #[test]
+  fn genesis_kitties_can_be_bought() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::buy_kitty(Origin::signed(2), dna(1), 100),
+        "The kitty is not for sale."
+      );
+      assert_ok!(Kitties::buy_kitty(Origin::signed(2), dna(2), 100));
+      assert_eq!(Kitties::owner_of(dna(2)), Some(2));
+      assert_eq!(Kitties::owned_kitty_count(1), 1);
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 10_100);
+    });
+  }
+
+  #[test]
+  fn burn_kitty_cleans_up_indexes() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(Kitties::burn_kitty(Origin::signed(2), dna(1)), "You do not own this kitty.");
+
+      assert_ok!(Kitties::burn_kitty(Origin::signed(1), dna(1)));
+
+      assert!(!<Kitties<Test>>::exists(dna(1)));
+      assert_eq!(Kitties::owner_of(dna(1)), None);
+
+      // The last kitty was swapped into the burned kitty's place
+      assert_eq!(Kitties::all_kitties_count(), 2);
+      assert_eq!(Kitties::all_kitties_by_index(0), dna(3));
+      assert_eq!(<AllKittiesIndex<Test>>::get(dna(3)), 0);
+      assert!(!<AllKittiesArray<Test>>::exists(2));
+
+      assert_eq!(Kitties::owned_kitty_count(1), 1);
+      assert_eq!(Kitties::owned_kitty_by_index((1, 0)), dna(2));
+      assert_eq!(<OwnedKittiesIndex<Test>>::get(dna(2)), 0);
+      assert!(!<OwnedKittiesArray<Test>>::exists((1, 1)));
+
+      assert_noop!(Kitties::burn_kitty(Origin::signed(1), dna(1)), "No owner for this kitty.");
+    });
+  }
+
+  #[test]
+  fn approved_account_can_transfer_once() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::transfer_from(Origin::signed(3), 1, 3, dna(1)),
+        "You are not allowed to transfer this kitty."
+      );
+      assert_noop!(Kitties::approve(Origin::signed(2), dna(1), 3), "You do not own or operate this kitty.");
+
+      assert_ok!(Kitties::approve(Origin::signed(1), dna(1), 3));
+      assert_eq!(Kitties::approved_of(dna(1)), Some(3));
+
+      assert_ok!(Kitties::transfer_from(Origin::signed(3), 1, 2, dna(1)));
+      assert_eq!(Kitties::owner_of(dna(1)), Some(2));
+      assert_eq!(Kitties::approved_of(dna(1)), None);
+
+      // The approval does not survive the change of ownership
+      assert_noop!(
+        Kitties::transfer_from(Origin::signed(3), 2, 3, dna(1)),
+        "You are not allowed to transfer this kitty."
+      );
+    });
+  }
+
+  #[test]
+  fn clear_approval_revokes_spender() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::approve(Origin::signed(1), dna(1), 3));
+      assert_ok!(Kitties::clear_approval(Origin::signed(1), dna(1)));
+      assert_noop!(
+        Kitties::transfer_from(Origin::signed(3), 1, 3, dna(1)),
+        "You are not allowed to transfer this kitty."
+      );
+    });
+  }
+
+  #[test]
+  fn operator_manages_all_kitties_of_owner() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(Kitties::transfer(Origin::signed(3), 3, dna(1)), "You do not own or operate this kitty.");
+
+      assert_ok!(Kitties::set_approval_for_all(Origin::signed(1), 3, true));
+      assert!(Kitties::is_approved_for_all((1, 3)));
+
+      assert_ok!(Kitties::set_price(Origin::signed(3), dna(2), 50));
+      assert_eq!(Kitties::kitty(dna(2)).price, 50);
+      assert_ok!(Kitties::transfer(Origin::signed(3), 2, dna(1)));
+      assert_ok!(Kitties::transfer_from(Origin::signed(3), 1, 3, dna(2)));
+      assert_eq!(Kitties::owner_of(dna(1)), Some(2));
+      assert_eq!(Kitties::owner_of(dna(2)), Some(3));
+
+      // Operator status is per owner, so it does not extend to the new owner's kitties
+      assert_noop!(Kitties::transfer(Origin::signed(3), 3, dna(1)), "You do not own or operate this kitty.");
+
+      assert_ok!(Kitties::set_approval_for_all(Origin::signed(1), 3, false));
+      assert!(!Kitties::is_approved_for_all((1, 3)));
+    });
+  }
+
+  #[test]
+  fn breeding_requires_matron_and_sire_permission() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(1), dna(1)),
+        "A kitty cannot breed with itself."
+      );
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(2), dna(1), dna(3)),
+        "You do not own the matron."
+      );
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(1), dna(3)),
+        "You are not allowed to breed with the sire."
+      );
+
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)));
+      assert!(Kitties::pregnancy_of(dna(1)).is_some());
+    });
+  }
+
+  #[test]
+  fn sire_allowance_is_used_up() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::allow_sire(Origin::signed(1), dna(3), 1),
+        "You do not own or operate this kitty."
+      );
+      assert_ok!(Kitties::allow_sire(Origin::signed(2), dna(3), 1));
+      assert_eq!(Kitties::sire_allowed_to(dna(3)), Some(1));
+
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(3)));
+      assert_eq!(Kitties::sire_allowed_to(dna(3)), None);
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(2), dna(3)),
+        "You are not allowed to breed with the sire."
+      );
+    });
+  }
+
+  #[test]
+  fn breed_with_sire_pays_the_sire_owner() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::breed_with_sire(Origin::signed(1), dna(1), dna(3), 500),
+        "The sire is not available for siring."
+      );
+      assert_noop!(
+        Kitties::set_sire_price(Origin::signed(1), dna(3), 300),
+        "You do not own or operate this kitty."
+      );
+      assert_ok!(Kitties::set_sire_price(Origin::signed(2), dna(3), 300));
+
+      assert_noop!(
+        Kitties::breed_with_sire(Origin::signed(1), dna(1), dna(3), 299),
+        "The sire costs more than the fee you offer."
+      );
+      assert_ok!(Kitties::breed_with_sire(Origin::signed(1), dna(1), dna(3), 500));
+
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 9_700);
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 10_300);
+      assert_eq!(Kitties::owner_of(dna(3)), Some(2));
+      assert_eq!(Kitties::pregnancy_of(dna(1)).unwrap().breeder, 1);
+      assert_eq!(Kitties::owned_kitty_count(2), 1);
+    });
+  }
+
+  #[test]
+  fn parents_cool_down_after_breeding() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)));
+
+      assert_eq!(Kitties::kitty(dna(1)).cooldown_end, 11);
+      assert_eq!(Kitties::kitty(dna(1)).breed_count, 1);
+      assert_eq!(Kitties::kitty(dna(2)).cooldown_end, 11);
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)),
+        "The matron is pregnant."
+      );
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(2), dna(1)),
+        "The matron is still cooling down."
+      );
+
+      // The second breeding makes both parents rest for longer
+      <system::Module<Test>>::set_block_number(11);
+      assert_ok!(Kitties::give_birth(Origin::signed(3), dna(1)));
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)));
+      assert_eq!(Kitties::kitty(dna(1)).cooldown_end, 31);
+    });
+  }
+
+  #[test]
+  fn cooldown_grows_with_generation_and_breedings() {
+    assert_eq!(<DefaultBreedingSchedule as BreedingSchedule<u64>>::cooldown(0, 0), 10);
+    assert_eq!(<DefaultBreedingSchedule as BreedingSchedule<u64>>::cooldown(4, 0), 50);
+    assert_eq!(<DefaultBreedingSchedule as BreedingSchedule<u64>>::cooldown(4, 3), 600);
+    assert_eq!(<DefaultBreedingSchedule as BreedingSchedule<u64>>::cooldown(100, 100), 100_800);
+  }
+
+  #[test]
+  fn kitties_stored_without_cooldown_still_decode() {
+    let legacy = (dna(7), dna(8), 42u64, 3u64).encode();
+    let kitty = <Kitty<H256, u64, u64> as Decode>::decode(&mut &legacy[..]).unwrap();
+
+    assert_eq!(kitty.dna, dna(8));
+    assert_eq!(kitty.price, 42);
+    assert_eq!(kitty.gen, 3);
+    assert_eq!(kitty.cooldown_end, 0);
+    assert_eq!(kitty.breed_count, 0);
+  }
+
+  #[test]
+  fn pregnant_matron_gives_birth_when_due() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)));
+
+      let pregnancy = Kitties::pregnancy_of(dna(1)).unwrap();
+      assert_eq!(pregnancy.sire, dna(2));
+      assert_eq!(pregnancy.conceived_at, 1);
+      assert_eq!(pregnancy.due_at, 11);
+      assert_eq!(Kitties::pregnancies_due(11), vec![dna(1)]);
+      assert_eq!(Kitties::owned_kitty_count(1), 2);
+
+      assert_noop!(Kitties::transfer(Origin::signed(1), 2, dna(1)), "This kitty is pregnant.");
+      assert_noop!(Kitties::set_price(Origin::signed(1), dna(1), 10), "This kitty is pregnant.");
+      assert_noop!(Kitties::burn_kitty(Origin::signed(1), dna(1)), "This kitty is pregnant.");
+      assert_noop!(
+        Kitties::give_birth(Origin::signed(1), dna(1)),
+        "The matron is not ready to give birth yet."
+      );
+
+      <system::Module<Test>>::set_block_number(11);
+      assert_ok!(Kitties::give_birth(Origin::signed(2), dna(1)));
+
+      assert_eq!(Kitties::pregnancy_of(dna(1)), None);
+      assert_eq!(Kitties::owned_kitty_count(1), 3);
+      let child = Kitties::kitty(Kitties::owned_kitty_by_index((1, 2)));
+      assert_eq!(child.gen, 1);
+      assert_ok!(Kitties::transfer(Origin::signed(1), 2, dna(1)));
+    });
+  }
+
+  #[test]
+  fn on_finalise_delivers_due_children() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)));
+
+      Kitties::on_finalise(10);
+      assert!(Kitties::pregnancy_of(dna(1)).is_some());
+
+      Kitties::on_finalise(11);
+      assert_eq!(Kitties::pregnancy_of(dna(1)), None);
+      assert_eq!(Kitties::owned_kitty_count(1), 3);
+      assert!(Kitties::pregnancies_due(11).is_empty());
+    });
+  }
+
+  #[test]
+  fn births_record_parentage() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)));
+      Kitties::on_finalise(11);
+
+      let child = Kitties::owned_kitty_by_index((1, 2));
+      assert_eq!(Kitties::parents_of(child), Some((dna(1), dna(2))));
+      assert_eq!(Kitties::child_count(dna(1)), 1);
+      assert_eq!(Kitties::child_by_index((dna(1), 0)), child);
+      assert_eq!(Kitties::child_count(dna(2)), 1);
+      assert_eq!(Kitties::child_by_index((dna(2), 0)), child);
+      assert_eq!(Kitties::parents_of(dna(1)), None);
+    });
+  }
+
+  #[test]
+  fn close_relatives_cannot_breed() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)));
+      <system::Module<Test>>::set_block_number(11);
+      Kitties::on_finalise(11);
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(2), dna(1)));
+      <system::Module<Test>>::set_block_number(31);
+      Kitties::on_finalise(21);
+
+      let first_child = Kitties::owned_kitty_by_index((1, 2));
+      let second_child = Kitties::owned_kitty_by_index((1, 3));
+
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), first_child, dna(1)),
+        "A kitty cannot breed with its parent."
+      );
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(2), second_child),
+        "A kitty cannot breed with its child."
+      );
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), first_child, second_child),
+        "Full siblings cannot breed."
+      );
+    });
+  }
+
+  #[test]
+  fn phenotype_of_decodes_kitty_dna() {
+    with_externalities(&mut new_test_ext(), || {
+      let phenotype = Kitties::phenotype_of(dna(1)).unwrap();
+      assert_eq!(phenotype.body_colour, crate::genetics::BodyColour::Salmon);
+      assert_eq!(phenotype.rarity, crate::genetics::Rarity::Common);
+
+      assert_eq!(Kitties::phenotype_of(dna(9)), None);
+    });
+  }
+
+  #[test]
+  fn unit_splicer_picks_bytes_by_seed_parity() {
+    assert_eq!(<() as GeneSplicer<H256>>::splice(&dna(1), &dna(2), &dna(4)), dna(2));
+    assert_eq!(<() as GeneSplicer<H256>>::splice(&dna(1), &dna(2), &dna(5)), dna(1));
+  }
+
+  #[test]
+  fn seed_with_nonce_never_repeats() {
+    with_externalities(&mut new_test_ext(), || {
+      let first = <SeedWithNonce<Test> as Randomness<H256>>::random(b"kitty");
+      let second = <SeedWithNonce<Test> as Randomness<H256>>::random(b"kitty");
+
+      assert_ne!(first, second);
+      assert_eq!(<Nonce<Test>>::get(), 2);
+    });
+  }
+
+  #[test]
+  fn create_kitty_uses_the_randomness_source() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::create_kitty(Origin::signed(3)));
+
+      let expected = (3u64.encode(), 0u64).using_encoded(BlakeTwo256::hash);
+      assert_eq!(Kitties::owned_kitty_by_index((3, 0)), expected);
+    });
+  }
+
+  #[test]
+  fn commit_reveal_creates_a_kitty() {
+    with_externalities(&mut new_test_ext(), || {
+      let secret = dna(42);
+      let commitment = BlakeTwo256::hash_of(&secret);
+
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::commit_create(Origin::signed(3), commitment));
+      assert_noop!(
+        Kitties::commit_create(Origin::signed(3), commitment),
+        "You already have an outstanding commitment."
+      );
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 100);
+      assert_noop!(
+        Kitties::reveal_create(Origin::signed(3), secret),
+        "The secret can only be revealed after the block following the commitment."
+      );
+
+      <system::Module<Test>>::set_block_number(2);
+      assert_noop!(
+        Kitties::reveal_create(Origin::signed(3), secret),
+        "The secret can only be revealed after the block following the commitment."
+      );
+      Kitties::on_finalise(2);
+
+      <system::Module<Test>>::set_block_number(3);
+      assert_noop!(
+        Kitties::reveal_breed(Origin::signed(3), secret),
+        "You have no breeding commitment."
+      );
+      assert_noop!(
+        Kitties::reveal_create(Origin::signed(3), dna(43)),
+        "The secret does not match your commitment."
+      );
+      assert_ok!(Kitties::reveal_create(Origin::signed(3), secret));
+
+      assert_eq!(Kitties::owned_kitty_count(3), 1);
+      assert_eq!(Kitties::commitment_of(3), None);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 0);
+    });
+  }
+
+  #[test]
+  fn reveals_use_the_seed_of_the_block_after_the_commitment() {
+    with_externalities(&mut new_test_ext(), || {
+      let secret = dna(42);
+
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::commit_create(Origin::signed(3), BlakeTwo256::hash_of(&secret)));
+      Kitties::on_finalise(1);
+      Kitties::on_finalise(2);
+      let block_seed = Kitties::reveal_seed_of(2).unwrap();
+
+      // Revealing late does not change the outcome
+      <system::Module<Test>>::set_block_number(6);
+      assert_ok!(Kitties::reveal_create(Origin::signed(3), secret));
+
+      let mut expected = (secret, block_seed).using_encoded(BlakeTwo256::hash);
+      genetics::strip_mutations(expected.as_mut());
+      let kitty_id = Kitties::owned_kitty_by_index((3, 0));
+      assert_eq!(Kitties::kitty(kitty_id).dna, expected);
+
+      // The seed is dropped once no commitment can use it
+      Kitties::on_finalise(7);
+      assert_eq!(Kitties::reveal_seed_of(2), None);
+    });
+  }
+
+  #[test]
+  fn commit_reveal_breeding_reserves_parents() {
+    with_externalities(&mut new_test_ext(), || {
+      let secret = dna(42);
+
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::commit_breed(Origin::signed(1), dna(1), dna(2), BlakeTwo256::hash_of(&secret)));
+      assert_eq!(Kitties::reserved_for_breeding(dna(1)), Some(1));
+
+      assert_noop!(Kitties::transfer(Origin::signed(1), 2, dna(2)), "This kitty is reserved for breeding.");
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(2), dna(1)),
+        "The matron is reserved for breeding."
+      );
+
+      Kitties::on_finalise(2);
+
+      <system::Module<Test>>::set_block_number(6);
+      assert_ok!(Kitties::reveal_breed(Origin::signed(1), secret));
+
+      assert_eq!(Kitties::reserved_for_breeding(dna(1)), None);
+      assert_eq!(Kitties::reserved_for_breeding(dna(2)), None);
+      let pregnancy = Kitties::pregnancy_of(dna(1)).unwrap();
+      assert_eq!(pregnancy.sire, dna(2));
+      assert!(pregnancy.seed.is_some());
+    });
+  }
+
+  #[test]
+  fn unrevealed_commitments_expire() {
+    with_externalities(&mut new_test_ext(), || {
+      let secret = dna(42);
+
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::commit_breed(Origin::signed(1), dna(1), dna(2), BlakeTwo256::hash_of(&secret)));
+
+      Kitties::on_finalise(5);
+      assert!(Kitties::commitment_of(1).is_some());
+
+      Kitties::on_finalise(6);
+      assert_eq!(Kitties::commitment_of(1), None);
+      assert_eq!(Kitties::reserved_for_breeding(dna(1)), None);
+      assert_eq!(Kitties::reserved_for_breeding(dna(2)), None);
+
+      // The bond is forfeited
+      assert_eq!(<balances::Module<Test>>::reserved_balance(1), 0);
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 9_900);
+
+      <system::Module<Test>>::set_block_number(7);
+      assert_noop!(
+        Kitties::reveal_breed(Origin::signed(1), secret),
+        "You have no outstanding commitment."
+      );
+      assert_ok!(Kitties::transfer(Origin::signed(1), 2, dna(2)));
+    });
+  }
+
+  #[test]
+  fn minting_respects_account_cap() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::set_max_kitties_per_account(Origin::signed(1), Some(2)),
+        "bad origin: expected to be a root origin"
+      );
+      assert_ok!(Kitties::set_max_kitties_per_account(system::RawOrigin::Root.into(), Some(2)));
+      assert_eq!(Kitties::max_kitties_per_account(), 2);
+
+      assert_noop!(Kitties::create_kitty(Origin::signed(1)), "This account owns too many kitties.");
+      assert_noop!(Kitties::transfer(Origin::signed(2), 1, dna(3)), "The receiver owns too many kitties.");
+
+      assert_ok!(Kitties::set_max_kitties_per_account(system::RawOrigin::Root.into(), None));
+      assert_eq!(Kitties::max_kitties_per_account(), 100);
+      assert_ok!(Kitties::create_kitty(Origin::signed(1)));
+    });
+  }
+
+  #[test]
+  fn minting_respects_gen0_supply() {
+    with_externalities(&mut new_test_ext(), || {
+      // Only the two gen 0 genesis kitties count
+      assert_eq!(Kitties::gen0_count(), 2);
+
+      assert_ok!(Kitties::set_max_gen0_supply(system::RawOrigin::Root.into(), Some(3)));
+      assert_ok!(Kitties::create_kitty(Origin::signed(3)));
+      assert_eq!(Kitties::gen0_count(), 3);
+      assert_noop!(Kitties::create_kitty(Origin::signed(3)), "The gen 0 supply is exhausted.");
+
+      // Burning does not free up supply, and bred kitties are not gen 0
+      assert_ok!(Kitties::burn_kitty(Origin::signed(1), dna(2)));
+      assert_noop!(Kitties::create_kitty(Origin::signed(3)), "The gen 0 supply is exhausted.");
+    });
+  }
+
+  #[test]
+  fn creation_deposit_follows_the_kitty() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::set_creation_deposit(system::RawOrigin::Root.into(), 1_000));
+
+      assert_ok!(Kitties::create_kitty(Origin::signed(3)));
+      let kitty_id = Kitties::owned_kitty_by_index((3, 0));
+      assert_eq!(Kitties::deposit_of(kitty_id), 1_000);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 1_000);
+
+      assert_ok!(Kitties::transfer(Origin::signed(3), 2, kitty_id));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 0);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 1_000);
+
+      assert_ok!(Kitties::burn_kitty(Origin::signed(2), kitty_id));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 0);
+      assert_eq!(Kitties::deposit_of(kitty_id), 0);
+    });
+  }
+
+  #[test]
+  fn creation_deposit_is_reserved_at_conception() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::set_creation_deposit(system::RawOrigin::Root.into(), 1_000));
+
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(1), 1_000);
+
+      <system::Module<Test>>::set_block_number(11);
+      assert_ok!(Kitties::give_birth(Origin::signed(3), dna(1)));
+      let child = Kitties::child_by_index((dna(1), 0));
+      assert_eq!(Kitties::deposit_of(child), 1_000);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(1), 1_000);
+    });
+  }
+
+  #[test]
+  fn creation_deposit_must_be_affordable() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::set_creation_deposit(system::RawOrigin::Root.into(), 20_000));
+
+      assert_noop!(Kitties::create_kitty(Origin::signed(3)), "You cannot afford the creation deposit.");
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)),
+        "You cannot afford the creation deposit."
+      );
+    });
+  }
+
+  #[test]
+  fn root_mints_promo_kitties() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::mint_promo(Origin::signed(1), 3, dna(0xf0)),
+        "bad origin: expected to be a root origin"
+      );
+
+      assert_ok!(Kitties::mint_promo(system::RawOrigin::Root.into(), 3, dna(0xf0)));
+      assert_eq!(Kitties::owner_of(dna(0xf0)), Some(3));
+      assert_eq!(Kitties::kitty(dna(0xf0)).dna, dna(0xf0));
+      assert_eq!(Kitties::gen0_count(), 3);
+
+      assert_noop!(
+        Kitties::mint_promo(system::RawOrigin::Root.into(), 3, dna(0xf0)),
+        "The kitty already exists"
+      );
+      assert_ok!(Kitties::mint_promo(system::RawOrigin::Root.into(), 3, dna(0xf1)));
+      assert_noop!(
+        Kitties::mint_promo(system::RawOrigin::Root.into(), 3, dna(0xf2)),
+        "The promo quota is exhausted."
+      );
+    });
+  }
+
+  #[test]
+  fn public_creation_can_be_disabled() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::set_public_creation(system::RawOrigin::Root.into(), false));
+
+      assert_noop!(Kitties::create_kitty(Origin::signed(3)), "Public kitty creation is disabled.");
+      assert_noop!(
+        Kitties::commit_create(Origin::signed(3), dna(42)),
+        "Public kitty creation is disabled."
+      );
+      assert_ok!(Kitties::mint_promo(system::RawOrigin::Root.into(), 3, dna(0xf0)));
+
+      assert_ok!(Kitties::set_public_creation(system::RawOrigin::Root.into(), true));
+      assert_ok!(Kitties::create_kitty(Origin::signed(3)));
+
+      // Commitments made while creation was enabled can still be revealed
+      let secret = dna(42);
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::commit_create(Origin::signed(2), BlakeTwo256::hash_of(&secret)));
+      Kitties::on_finalise(2);
+      assert_ok!(Kitties::set_public_creation(system::RawOrigin::Root.into(), false));
+
+      <system::Module<Test>>::set_block_number(3);
+      assert_ok!(Kitties::reveal_create(Origin::signed(2), secret));
+      assert_eq!(Kitties::owned_kitty_count(2), 2);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 0);
+    });
+  }
+
+  #[test]
+  fn dutch_auction_price_falls() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(10);
+      assert_noop!(
+        Kitties::create_dutch_auction(Origin::signed(1), dna(1), 100, 200, 10),
+        "The price of a dutch auction cannot rise."
+      );
+      assert_ok!(Kitties::create_dutch_auction(Origin::signed(1), dna(1), 1_000, 200, 10));
+
+      assert_noop!(Kitties::transfer(Origin::signed(1), 3, dna(1)), "This kitty is on auction.");
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)),
+        "The matron is on auction."
+      );
+
+      <system::Module<Test>>::set_block_number(15);
+      assert_noop!(
+        Kitties::bid_dutch(Origin::signed(2), dna(1), 599),
+        "The kitty costs more than the price you offer."
+      );
+      assert_ok!(Kitties::bid_dutch(Origin::signed(2), dna(1), 600));
+
+      assert_eq!(Kitties::owner_of(dna(1)), Some(2));
+      assert_eq!(Kitties::dutch_auction_of(dna(1)), None);
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 10_600);
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 9_400);
+    });
+  }
+
+  #[test]
+  fn dutch_auction_bottoms_out_and_can_be_cancelled() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::create_dutch_auction(Origin::signed(1), dna(2), 1_000, 200, 10));
+      // The fixed price listing of the kitty was dropped
+      assert_eq!(Kitties::kitty(dna(2)).price, 0);
+
+      <system::Module<Test>>::set_block_number(50);
+      let auction = Kitties::dutch_auction_of(dna(2)).unwrap();
+      assert_eq!(Kitties::_dutch_price(&auction, 50), 200);
+
+      assert_noop!(
+        Kitties::cancel_dutch_auction(Origin::signed(2), dna(2)),
+        "You do not own or operate this kitty."
+      );
+      assert_ok!(Kitties::cancel_dutch_auction(Origin::signed(1), dna(2)));
+      assert_noop!(Kitties::bid_dutch(Origin::signed(2), dna(2), 1_000), "This kitty is not on auction.");
+      assert_ok!(Kitties::transfer(Origin::signed(1), 3, dna(2)));
+    });
+  }
+
+  #[test]
+  fn dutch_auction_price_handles_large_prices() {
+    with_externalities(&mut new_test_ext(), || {
+      let auction = DutchAuction {
+        start_price: u64::max_value(),
+        end_price: 0,
+        started_at: 0,
+        duration: 10,
+      };
+
+      assert_eq!(Kitties::_dutch_price(&auction, 0), u64::max_value());
+      assert_eq!(Kitties::_dutch_price(&auction, 5), 9_223_372_036_854_775_808);
+      assert_eq!(Kitties::_dutch_price(&auction, 10), 0);
+    });
+  }
+
+  #[test]
+  fn english_auction_sells_to_the_top_bidder() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::create_auction(Origin::signed(1), dna(1), 500, 10));
+      assert_noop!(Kitties::transfer(Origin::signed(1), 3, dna(1)), "This kitty is on auction.");
+
+      assert_ok!(Kitties::bid(Origin::signed(2), dna(1), 400));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 400);
+      assert_noop!(
+        Kitties::bid(Origin::signed(3), dna(1), 400),
+        "Your bid must be higher than the top bid."
+      );
+      assert_ok!(Kitties::bid(Origin::signed(3), dna(1), 700));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 0);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 700);
+
+      <system::Module<Test>>::set_block_number(10);
+      assert_noop!(Kitties::bid(Origin::signed(2), dna(1), 800), "The auction has ended.");
+
+      Kitties::on_finalise(10);
+      assert_eq!(Kitties::auction_of(dna(1)), None);
+      assert_eq!(Kitties::owner_of(dna(1)), Some(3));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 0);
+      assert_eq!(<balances::Module<Test>>::free_balance(3), 9_300);
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 10_700);
+    });
+  }
+
+  #[test]
+  fn english_auction_below_reserve_returns_the_kitty() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_noop!(
+        Kitties::create_auction(Origin::signed(1), dna(1), 500, 1),
+        "The auction must end in a later block."
+      );
+      assert_noop!(
+        Kitties::create_auction(Origin::signed(1), dna(1), 500, 102),
+        "The auction ends too far in the future."
+      );
+      assert_ok!(Kitties::create_auction(Origin::signed(1), dna(1), 500, 10));
+      assert_ok!(Kitties::bid(Origin::signed(2), dna(1), 400));
+
+      Kitties::on_finalise(10);
+      assert_eq!(Kitties::auction_of(dna(1)), None);
+      assert_eq!(Kitties::owner_of(dna(1)), Some(1));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 0);
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 10_000);
+      assert_ok!(Kitties::transfer(Origin::signed(1), 3, dna(1)));
+    });
+  }
+
+  #[test]
+  fn accepted_offers_swap_funds_and_kitty() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_noop!(
+        Kitties::make_offer(Origin::signed(1), dna(1), 500, 10),
+        "You cannot make an offer on your own kitty."
+      );
+      assert_ok!(Kitties::make_offer(Origin::signed(2), dna(1), 500, 10));
+      assert_ok!(Kitties::make_offer(Origin::signed(3), dna(1), 300, 10));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 500);
+      assert_noop!(
+        Kitties::make_offer(Origin::signed(2), dna(1), 600, 10),
+        "You already made an offer on this kitty."
+      );
+
+      assert_noop!(
+        Kitties::accept_offer(Origin::signed(2), dna(1), 2),
+        "You do not own or operate this kitty."
+      );
+      assert_ok!(Kitties::accept_offer(Origin::signed(1), dna(1), 2));
+
+      assert_eq!(Kitties::owner_of(dna(1)), Some(2));
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 10_500);
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 9_500);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 0);
+
+      // The other offer now stands for the new owner
+      assert_eq!(Kitties::offers_for(dna(1)), vec![3]);
+      assert_ok!(Kitties::accept_offer(Origin::signed(2), dna(1), 3));
+      assert_eq!(Kitties::owner_of(dna(1)), Some(3));
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 9_800);
+    });
+  }
+
+  #[test]
+  fn offers_are_withdrawn() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::make_offer(Origin::signed(2), dna(1), 500, 10));
+      assert_ok!(Kitties::cancel_offer(Origin::signed(2), dna(1)));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 0);
+      assert_noop!(Kitties::cancel_offer(Origin::signed(2), dna(1)), "There is no such offer.");
+
+      // Expiry
+      assert_ok!(Kitties::make_offer(Origin::signed(2), dna(1), 500, 10));
+      Kitties::on_finalise(10);
+      assert_eq!(Kitties::offer_of((dna(1), 2)), None);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 0);
+
+      // Rejection by the owner
+      assert_ok!(Kitties::make_offer(Origin::signed(2), dna(1), 500, 10));
+      assert_noop!(
+        Kitties::reject_offer(Origin::signed(3), dna(1), 2),
+        "You do not own or operate this kitty."
+      );
+      assert_ok!(Kitties::reject_offer(Origin::signed(1), dna(1), 2));
+      assert_eq!(Kitties::offer_of((dna(1), 2)), None);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 0);
+      assert_noop!(Kitties::reject_offer(Origin::signed(1), dna(1), 2), "There is no such offer.");
+
+      // Receiving the kitty
+      assert_ok!(Kitties::make_offer(Origin::signed(2), dna(1), 500, 20));
+      assert_ok!(Kitties::transfer(Origin::signed(1), 2, dna(1)));
+      assert_eq!(Kitties::offer_of((dna(1), 2)), None);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 0);
+
+      // Burning the kitty
+      assert_ok!(Kitties::make_offer(Origin::signed(3), dna(1), 500, 20));
+      assert_ok!(Kitties::burn_kitty(Origin::signed(2), dna(1)));
+      assert!(Kitties::offers_for(dna(1)).is_empty());
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 0);
+    });
+  }
+
+  #[test]
+  fn creators_earn_royalties_on_resales() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::set_royalty(Origin::signed(1), Permill::from_percent(20)),
+        "The royalty is higher than allowed."
+      );
+      assert_ok!(Kitties::set_royalty(Origin::signed(1), Permill::from_percent(10)));
+      assert_eq!(Kitties::creator_of(dna(2)), Some(1));
+
+      // No royalty when the creator sells
+      assert_ok!(Kitties::buy_kitty(Origin::signed(2), dna(2), 100));
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 10_100);
+
+      assert_ok!(Kitties::set_price(Origin::signed(2), dna(2), 1_000));
+      assert_ok!(Kitties::buy_kitty(Origin::signed(3), dna(2), 1_000));
+
+      assert_eq!(Kitties::owner_of(dna(2)), Some(3));
+      assert_eq!(<balances::Module<Test>>::free_balance(3), 9_000);
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 9_900 + 900);
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 10_100 + 100);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 0);
+    });
+  }
+
+  #[test]
+  fn sales_pay_the_market_fee() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::set_market_fee(Origin::signed(1), Permill::from_percent(5)),
+        "bad origin: expected to be a root origin"
+      );
+      assert_noop!(
+        Kitties::set_market_fee(system::RawOrigin::Root.into(), Permill::from_percent(95)),
+        "The market fee leaves no room for royalties."
+      );
+      assert_ok!(Kitties::set_market_fee(system::RawOrigin::Root.into(), Permill::from_percent(5)));
+      assert_ok!(Kitties::set_royalty(Origin::signed(1), Permill::from_percent(10)));
+
+      // Fixed price sale by the creator
+      assert_ok!(Kitties::set_price(Origin::signed(1), dna(2), 1_000));
+      assert_ok!(Kitties::buy_kitty(Origin::signed(2), dna(2), 1_000));
+      assert_eq!(<balances::Module<Test>>::free_balance(FEE_COLLECTOR), 1 + 50);
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 10_950);
+
+      // Accepted offer, with a royalty
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::make_offer(Origin::signed(3), dna(2), 2_000, 10));
+      assert_ok!(Kitties::accept_offer(Origin::signed(2), dna(2), 3));
+      assert_eq!(<balances::Module<Test>>::free_balance(FEE_COLLECTOR), 1 + 50 + 100);
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 10_950 + 200);
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 9_000 + 1_700);
+    });
+  }
+
+  #[test]
+  fn listings_expire() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_noop!(
+        Kitties::set_price_with_expiry(Origin::signed(1), dna(1), 500, 1),
+        "The listing must expire in a later block."
+      );
+      assert_ok!(Kitties::set_price_with_expiry(Origin::signed(1), dna(1), 500, 10));
+      assert_eq!(Kitties::listing_expiry_of(dna(1)), Some(10));
+
+      <system::Module<Test>>::set_block_number(10);
+      assert_noop!(Kitties::buy_kitty(Origin::signed(2), dna(1), 500), "This listing has expired.");
+
+      Kitties::on_finalise(10);
+      assert_eq!(Kitties::kitty(dna(1)).price, 0);
+      assert_eq!(Kitties::listing_expiry_of(dna(1)), None);
+      assert_noop!(Kitties::buy_kitty(Origin::signed(2), dna(1), 500), "The kitty is not for sale.");
+    });
+  }
+
+  #[test]
+  fn relisting_replaces_the_expiry() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::set_price_with_expiry(Origin::signed(1), dna(1), 500, 10));
+      assert_ok!(Kitties::set_price(Origin::signed(1), dna(1), 600));
+
+      Kitties::on_finalise(10);
+      assert_eq!(Kitties::kitty(dna(1)).price, 600);
+
+      <system::Module<Test>>::set_block_number(11);
+      assert_ok!(Kitties::buy_kitty(Origin::signed(2), dna(1), 600));
+    });
+  }
+
+  #[test]
+  fn transfer_batch_moves_all_or_nothing() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_noop!(
+        Kitties::transfer_batch(Origin::signed(1), 3, vec![]),
+        "There are no kitties to transfer."
+      );
+      assert_noop!(
+        Kitties::transfer_batch(Origin::signed(1), 3, vec![dna(1); MAX_TRANSFER_BATCH + 1]),
+        "Too many kitties to transfer at once."
+      );
+      assert_noop!(
+        Kitties::transfer_batch(Origin::signed(1), 3, vec![dna(1), dna(1)]),
+        "A kitty appears twice in the batch."
+      );
+      assert_noop!(
+        Kitties::transfer_batch(Origin::signed(1), 3, vec![dna(1), dna(3)]),
+        "You do not own or operate this kitty."
+      );
+      assert_noop!(
+        Kitties::transfer_batch(Origin::signed(1), 1, vec![dna(1), dna(2)]),
+        "You cannot transfer a kitty to its owner."
+      );
+
+      // An operator cannot send kitties back to their owner either
+      assert_ok!(Kitties::set_approval_for_all(Origin::signed(1), 2, true));
+      assert_noop!(
+        Kitties::transfer_batch(Origin::signed(2), 1, vec![dna(1)]),
+        "You cannot transfer a kitty to its owner."
+      );
+      assert_noop!(Kitties::transfer(Origin::signed(2), 1, dna(1)), "You cannot transfer a kitty to its owner.");
+      assert_noop!(Kitties::transfer(Origin::signed(1), 1, dna(1)), "You cannot transfer a kitty to its owner.");
+      assert_eq!(Kitties::owned_kitty_by_index((1, 0)), dna(1));
+      assert_eq!(Kitties::owned_kitty_by_index((1, 1)), dna(2));
+
+      assert_ok!(Kitties::set_max_kitties_per_account(system::RawOrigin::Root.into(), Some(1)));
+      assert_noop!(
+        Kitties::transfer_batch(Origin::signed(1), 3, vec![dna(1), dna(2)]),
+        "The receiver owns too many kitties."
+      );
+      assert_ok!(Kitties::set_max_kitties_per_account(system::RawOrigin::Root.into(), None));
+
+      // Kitty 2 is listed, but the listing does not follow it to the receiver
+      assert_eq!(Kitties::kitty(dna(2)).price, 100);
+      assert_ok!(Kitties::transfer_batch(Origin::signed(1), 3, vec![dna(1), dna(2)]));
+      assert_eq!(Kitties::owner_of(dna(1)), Some(3));
+      assert_eq!(Kitties::owner_of(dna(2)), Some(3));
+      assert_eq!(Kitties::owned_kitty_count(1), 0);
+      assert_eq!(Kitties::owned_kitty_count(3), 2);
+      assert_eq!(Kitties::kitty(dna(2)).price, 0);
+      assert_noop!(Kitties::buy_kitty(Origin::signed(2), dna(2), 100), "The kitty is not for sale.");
+    });
+  }
+
+  #[test]
+  fn swaps_trade_kitties_and_money() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_noop!(
+        Kitties::propose_swap(Origin::signed(1), vec![dna(3)], vec![], BalanceDelta::Nothing, 2, 10),
+        "You do not own this kitty."
+      );
+      // Kitty 2 is listed from genesis, and the counterparty lists kitty 3
+      assert_ok!(Kitties::set_price(Origin::signed(2), dna(3), 500));
+      assert_ok!(Kitties::propose_swap(
+        Origin::signed(1), vec![dna(1), dna(2)], vec![dna(3)], BalanceDelta::ProposerPays(300), 2, 10
+      ));
+      let swap_id = Kitties::swap_locking(dna(1)).unwrap();
+      assert_eq!(Kitties::kitty(dna(2)).price, 0);
+
+      assert_eq!(<balances::Module<Test>>::reserved_balance(1), 300);
+      assert_noop!(Kitties::transfer(Origin::signed(1), 3, dna(1)), "This kitty is locked in a swap.");
+      assert_noop!(Kitties::accept_swap(Origin::signed(3), swap_id), "This swap is not for you.");
+
+      assert_ok!(Kitties::accept_swap(Origin::signed(2), swap_id));
+      assert_eq!(Kitties::owner_of(dna(1)), Some(2));
+      assert_eq!(Kitties::owner_of(dna(2)), Some(2));
+      assert_eq!(Kitties::owner_of(dna(3)), Some(1));
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 9_700);
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 10_300);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(1), 0);
+      assert_eq!(Kitties::swap_of(swap_id), None);
+      assert_eq!(Kitties::swap_locking(dna(1)), None);
+
+      // Neither side's listing survives the swap
+      assert_eq!(Kitties::kitty(dna(3)).price, 0);
+      assert_noop!(Kitties::buy_kitty(Origin::signed(3), dna(3), 500), "The kitty is not for sale.");
+      assert_noop!(Kitties::buy_kitty(Origin::signed(3), dna(2), 100), "The kitty is not for sale.");
+    });
+  }
+
+  #[test]
+  fn swaps_can_be_cancelled_or_expire() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::propose_swap(
+        Origin::signed(1), vec![dna(1)], vec![dna(3)], BalanceDelta::CounterpartyPays(300), 2, 10
+      ));
+      let swap_id = Kitties::swap_locking(dna(1)).unwrap();
+
+      assert_noop!(Kitties::cancel_swap(Origin::signed(2), swap_id), "You did not propose this swap.");
+      assert_ok!(Kitties::cancel_swap(Origin::signed(1), swap_id));
+      assert_eq!(Kitties::swap_locking(dna(1)), None);
+      assert_noop!(Kitties::accept_swap(Origin::signed(2), swap_id), "This swap does not exist.");
+
+      assert_ok!(Kitties::propose_swap(
+        Origin::signed(1), vec![dna(1)], vec![], BalanceDelta::ProposerPays(300), 2, 10
+      ));
+      let swap_id = Kitties::swap_locking(dna(1)).unwrap();
+
+      Kitties::on_finalise(10);
+      assert_eq!(Kitties::swap_of(swap_id), None);
+      assert_eq!(Kitties::swap_locking(dna(1)), None);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(1), 0);
+      assert_ok!(Kitties::transfer(Origin::signed(1), 3, dna(1)));
+    });
+  }
+
+  #[test]
+  fn operators_breed_for_the_owner() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::set_creation_deposit(system::RawOrigin::Root.into(), 1_000));
+      assert_ok!(Kitties::set_approval_for_all(Origin::signed(1), 3, true));
+
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::breed_kitty(Origin::signed(3), dna(1), dna(2)));
+      assert_eq!(Kitties::pregnancy_of(dna(1)).unwrap().breeder, 1);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(1), 1_000);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 0);
+
+      Kitties::on_finalise(11);
+      assert_eq!(Kitties::owned_kitty_count(1), 3);
+      assert_eq!(Kitties::owned_kitty_count(3), 0);
+    });
+  }
+
+  #[test]
+  fn births_respect_the_owner_cap() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::set_creation_deposit(system::RawOrigin::Root.into(), 1_000));
+      assert_ok!(Kitties::set_max_kitties_per_account(system::RawOrigin::Root.into(), Some(2)));
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)),
+        "The matron's owner cannot take another kitty."
+      );
+
+      assert_ok!(Kitties::set_max_kitties_per_account(system::RawOrigin::Root.into(), Some(3)));
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)));
+
+      // The owner fills up their inventory during gestation
+      assert_ok!(Kitties::transfer(Origin::signed(2), 1, dna(3)));
+
+      Kitties::on_finalise(11);
+      assert_eq!(Kitties::pregnancy_of(dna(1)), None);
+      assert_eq!(Kitties::owned_kitty_count(1), 3);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(1), 0);
+      assert_ok!(Kitties::transfer(Origin::signed(1), 2, dna(1)));
+    });
+  }
+
+  #[test]
+  fn full_offer_lists_drop_the_lowest_offer() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_noop!(
+        Kitties::make_offer(Origin::signed(2), dna(1), 500, 102),
+        "The offer expires too far in the future."
+      );
+
+      for buyer in 10..10 + MAX_OFFERS_PER_KITTY as u64 {
+        <balances::Module<Test>>::set_free_balance(&buyer, 1_000);
+        assert_ok!(Kitties::make_offer(Origin::signed(buyer), dna(1), buyer, 101));
+      }
+      assert_eq!(Kitties::offers_for(dna(1)).len(), MAX_OFFERS_PER_KITTY);
+
+      assert_noop!(
+        Kitties::make_offer(Origin::signed(2), dna(1), 10, 101),
+        "Your offer is lower than every other offer on this kitty."
+      );
+      assert_ok!(Kitties::make_offer(Origin::signed(2), dna(1), 11, 101));
+
+      assert_eq!(Kitties::offers_for(dna(1)).len(), MAX_OFFERS_PER_KITTY);
+      assert_eq!(Kitties::offer_of((dna(1), 10)), None);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(10), 0);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(2), 11);
+    });
+  }
+
+  #[test]
+  fn sales_to_missing_accounts_fail_before_anything_moves() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::set_price(Origin::signed(2), dna(3), 1_000));
+      assert_ok!(Kitties::make_offer(Origin::signed(3), dna(3), 500, 10));
+
+      // The seller's account is reaped
+      assert_ok!(<balances::Module<Test>>::make_transfer(&2, &1, 10_000));
+      assert_eq!(<balances::Module<Test>>::total_balance(&2), 0);
+
+      assert_noop!(
+        Kitties::buy_kitty(Origin::signed(3), dna(3), 1_000),
+        "The seller's account does not exist."
+      );
+      assert_noop!(
+        Kitties::accept_offer(Origin::signed(2), dna(3), 3),
+        "The seller's account does not exist."
+      );
+      assert_eq!(Kitties::owner_of(dna(3)), Some(2));
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 500);
+    });
+  }
+
+  #[test]
+  fn royalties_are_skipped_for_missing_creators() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::set_royalty(Origin::signed(1), Permill::from_percent(10)));
+      assert_ok!(Kitties::buy_kitty(Origin::signed(2), dna(2), 100));
+      assert_ok!(Kitties::set_price(Origin::signed(2), dna(2), 1_000));
+
+      // The creator's account is reaped
+      assert_ok!(<balances::Module<Test>>::make_transfer(&1, &3, 10_100));
+      assert_eq!(<balances::Module<Test>>::total_balance(&1), 0);
+
+      assert_ok!(Kitties::buy_kitty(Origin::signed(3), dna(2), 1_000));
+      assert_eq!(Kitties::owner_of(dna(2)), Some(3));
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 9_900 + 1_000);
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 0);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(3), 0);
+    });
+  }
+
+  #[test]
+  #[should_panic(expected = "The genesis market fee leaves no room for royalties.")]
+  fn genesis_market_fee_is_bounded() {
+    let _ = GenesisConfig::<Test> {
+      kitties: vec![],
+      creation_deposit: 0,
+      public_creation_enabled: true,
+      instant_randomness_enabled: true,
+      market_fee: Permill::from_percent(95),
+      fee_collector: FEE_COLLECTOR,
+    }
+    .build_storage();
+  }
+
+  #[test]
+  fn swaps_only_check_final_kitty_counts() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      // Account 1 is at the cap with two kitties
+      assert_ok!(Kitties::set_max_kitties_per_account(system::RawOrigin::Root.into(), Some(2)));
+
+      assert_ok!(Kitties::propose_swap(Origin::signed(2), vec![dna(3)], vec![], BalanceDelta::Nothing, 1, 10));
+      let gift_id = Kitties::swap_locking(dna(3)).unwrap();
+      assert_noop!(Kitties::accept_swap(Origin::signed(1), gift_id), "You would own too many kitties.");
+      assert_ok!(Kitties::cancel_swap(Origin::signed(2), gift_id));
+
+      assert_ok!(Kitties::propose_swap(
+        Origin::signed(2), vec![dna(3)], vec![dna(1)], BalanceDelta::CounterpartyPays(300), 1, 10
+      ));
+      let swap_id = Kitties::swap_locking(dna(3)).unwrap();
+      assert_ok!(Kitties::accept_swap(Origin::signed(1), swap_id));
+
+      assert_eq!(Kitties::owner_of(dna(1)), Some(2));
+      assert_eq!(Kitties::owner_of(dna(3)), Some(1));
+      assert_eq!(Kitties::owned_kitty_count(1), 2);
+      assert_eq!(<balances::Module<Test>>::free_balance(1), 9_700);
+      assert_eq!(<balances::Module<Test>>::free_balance(2), 10_300);
+      assert_eq!(<balances::Module<Test>>::reserved_balance(1), 0);
+      assert_eq!(Kitties::swap_of(swap_id), None);
+    });
+  }
+
+  #[test]
+  #[should_panic(expected = "The kitty already exists")]
+  fn genesis_reports_why_a_kitty_cannot_be_minted() {
+    let _ = GenesisConfig::<Test> {
+      kitties: vec![(1, dna(1), 0, 0), (2, dna(1), 0, 0)],
+      creation_deposit: 0,
+      public_creation_enabled: true,
+      instant_randomness_enabled: true,
+      market_fee: Permill::zero(),
+      fee_collector: FEE_COLLECTOR,
+    }
+    .build_storage();
+  }
+
+  #[test]
+  fn commitment_expiries_are_spread_over_blocks() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      let committers = 10..10 + MAX_COMMITMENT_EXPIRIES_PER_BLOCK as u64 + 1;
+      for who in committers.clone() {
+        <balances::Module<Test>>::set_free_balance(&who, 1_000);
+        assert_ok!(Kitties::commit_create(Origin::signed(who), dna(42)));
+      }
+
+      Kitties::on_finalise(6);
+      let left: Vec<u64> = committers.clone().filter(|who| Kitties::commitment_of(who).is_some()).collect();
+      assert_eq!(left.len(), 1);
+      assert_eq!(Kitties::commitments_expiring(7), left);
+
+      Kitties::on_finalise(7);
+      assert!(committers.clone().all(|who| Kitties::commitment_of(who).is_none()));
+      assert!(committers.clone().all(|who| <balances::Module<Test>>::free_balance(who) == 900));
+    });
+  }
+
+  #[test]
+  fn instant_randomness_can_be_disabled() {
+    with_externalities(&mut new_test_ext(), || {
+      assert_ok!(Kitties::set_sire_price(Origin::signed(2), dna(3), 300));
+      assert_noop!(
+        Kitties::set_instant_randomness(Origin::signed(1), false),
+        "bad origin: expected to be a root origin"
+      );
+      assert_ok!(Kitties::set_instant_randomness(system::RawOrigin::Root.into(), false));
+
+      assert_noop!(Kitties::create_kitty(Origin::signed(3)), "Use commit_create and reveal_create instead.");
+      assert_noop!(
+        Kitties::breed_kitty(Origin::signed(1), dna(1), dna(2)),
+        "Use commit_breed and reveal_breed instead."
+      );
+      assert_noop!(
+        Kitties::breed_with_sire(Origin::signed(1), dna(1), dna(3), 300),
+        "Use commit_breed and reveal_breed instead."
+      );
+      assert_ok!(Kitties::commit_create(Origin::signed(3), dna(42)));
+    });
+  }
+
+  #[test]
+  fn auctions_without_bids_can_be_cancelled() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::create_auction(Origin::signed(1), dna(1), 500, 10));
+      assert_ok!(Kitties::create_auction(Origin::signed(1), dna(2), 500, 10));
+      assert_ok!(Kitties::bid(Origin::signed(2), dna(2), 600));
+
+      assert_noop!(
+        Kitties::cancel_auction(Origin::signed(2), dna(1)),
+        "You do not own or operate this kitty."
+      );
+      assert_noop!(Kitties::cancel_auction(Origin::signed(1), dna(2)), "The auction already has bids.");
+      assert_ok!(Kitties::cancel_auction(Origin::signed(1), dna(1)));
+      assert_noop!(Kitties::cancel_auction(Origin::signed(1), dna(1)), "This kitty is not on auction.");
+
+      assert_eq!(Kitties::auction_of(dna(1)), None);
+      assert_ok!(Kitties::transfer(Origin::signed(1), 3, dna(1)));
+
+      // Settling skips the cancelled auction
+      Kitties::on_finalise(10);
+      assert_eq!(Kitties::owner_of(dna(1)), Some(3));
+      assert_eq!(Kitties::owner_of(dna(2)), Some(2));
+    });
+  }
+
+  #[test]
+  fn accepting_an_offer_ends_the_listing() {
+    with_externalities(&mut new_test_ext(), || {
+      <system::Module<Test>>::set_block_number(1);
+      assert_ok!(Kitties::set_price_with_expiry(Origin::signed(1), dna(1), 1_000, 10));
+      assert_ok!(Kitties::make_offer(Origin::signed(2), dna(1), 500, 10));
+      assert_ok!(Kitties::accept_offer(Origin::signed(1), dna(1), 2));
+
+      assert_eq!(Kitties::kitty(dna(1)).price, 0);
+      assert_eq!(Kitties::listing_expiry_of(dna(1)), None);
+      assert_noop!(Kitties::buy_kitty(Origin::signed(3), dna(1), 1_000), "The kitty is not for sale.");
+      assert_eq!(Kitties::owner_of(dna(1)), Some(2));
+    });
+  }
+}
@@ -1,7 +1,7 @@
 use primitives::{Ed25519AuthorityId, ed25519};
 use node_template_runtime::{
 	AccountId, GenesisConfig, ConsensusConfig, TimestampConfig, BalancesConfig,
-	SudoConfig, IndicesConfig, FeesConfig,
+	SudoConfig, IndicesConfig, FeesConfig, SubstratekittiesConfig, Permill,
 };
 use substrate_service;
 
@@ -76,7 +76,14 @@ impl Alternative {
 	}
 }
 
+fn account_id(seed: &[u8; 32]) -> AccountId {
+	ed25519::Pair::from_seed(seed).public().0.into()
+}
+
 fn testnet_genesis(initial_authorities: Vec<Ed25519AuthorityId>, endowed_accounts: Vec<AccountId>, root_key: AccountId) -> GenesisConfig {
+	let alice = account_id(b"Alice                           ");
+	let bob = account_id(b"Bob                             ");
+
 	GenesisConfig {
 		consensus: Some(ConsensusConfig {
 			code: include_bytes!("../runtime/wasm/target/wasm32-unknown-unknown/release/node_template_runtime_wasm.compact.wasm").to_vec(),
@@ -102,6 +109,19 @@ fn testnet_genesis(initial_authorities: Vec<Ed25519AuthorityId>, endowed_account
 		fees: Some(FeesConfig {
 			transaction_base_fee: 1,
 			transaction_byte_fee: 0,
-		})
+		}),
+		substratekitties: Some(SubstratekittiesConfig {
+			kitties: vec![
+				(alice, [0x11; 32].into(), 0, 0),
+				(alice, [0x22; 32].into(), 1 << 40, 0),
+				(bob, [0x33; 32].into(), 0, 0),
+				(bob, [0x44; 32].into(), 1 << 40, 1),
+			],
+			creation_deposit: 1 << 30,
+			public_creation_enabled: true,
+			instant_randomness_enabled: false,
+			market_fee: Permill::from_percent(2),
+			fee_collector: alice,
+		}),
 	}
 }